@@ -1,5 +1,5 @@
 use crate::editor;
-use crate::program::{Mode, Program};
+use crate::program::{Mode, Program, Prompt};
 use crate::ui;
 
 use anyhow::Result;
@@ -13,17 +13,25 @@ use std::sync::{
     Arc, Mutex,
 };
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 type SharedState = Arc<Mutex<Program>>;
 
+/// How long the runner holds the state lock before letting the UI and keys in
+const RUN_BATCH_DURATION: Duration = Duration::from_millis(5);
+/// How long the runner waits between batches. The lock isn't fair, so without a pause the
+/// runner can take it straight back and starve key handling and redraws.
+const RUN_HANDOFF_DURATION: Duration = Duration::from_millis(1);
+
 pub fn run(program: Program) -> Result<()> {
     let shared_state = Arc::new(Mutex::new(program));
     let (tx_program, rx_program) = mpsc::channel::<KeyEvent>();
+    let (tx_runner, rx_runner) = mpsc::channel::<()>();
     let (tx_ui, rx_ui) = mpsc::channel::<()>();
 
     spawn_input_thread(tx_program);
-    spawn_program_thread(shared_state.clone(), rx_program, tx_ui);
+    spawn_runner_thread(shared_state.clone(), rx_runner);
+    spawn_program_thread(shared_state.clone(), rx_program, tx_runner, tx_ui);
     ui_loop(shared_state, rx_ui)
 }
 
@@ -60,9 +68,28 @@ fn read_buffered_keys() -> Vec<KeyCode> {
     keys
 }
 
+/// Advances a running program in short batches, releasing the lock between each one
+pub fn spawn_runner_thread(shared_state: SharedState, rx_runner: Receiver<()>) {
+    thread::spawn(move || {
+        while let Ok(()) = rx_runner.recv() {
+            loop {
+                let mut guard = shared_state.lock().unwrap();
+                let running = guard.run_batch(RUN_BATCH_DURATION);
+                drop(guard);
+
+                if !running {
+                    break;
+                }
+                thread::sleep(RUN_HANDOFF_DURATION);
+            }
+        }
+    });
+}
+
 pub fn spawn_program_thread(
     shared_state: SharedState,
     rx_program: Receiver<KeyEvent>,
+    tx_runner: Sender<()>,
     tx_ui: Sender<()>,
 ) {
     thread::spawn(move || loop {
@@ -71,6 +98,8 @@ pub fn spawn_program_thread(
             let program = &mut guard;
             let control = event.modifiers.contains(KeyModifiers::CONTROL);
             let shift = event.modifiers.contains(KeyModifiers::SHIFT);
+            let alt = event.modifiers.contains(KeyModifiers::ALT);
+            let was_running = program.is_running_mode();
 
             match program.mode {
                 Mode::Editor => match event.code {
@@ -80,6 +109,90 @@ pub fn spawn_program_thread(
                     KeyCode::Char('s') if control => {
                         program.editor.save().ok();
                     }
+                    KeyCode::Char('e') if control => {
+                        program.cycle_eof();
+                    }
+                    KeyCode::Char('p') if control => {
+                        program.start_playing();
+                    }
+                    KeyCode::Char('b') if control => {
+                        program.toggle_breakpoint();
+                    }
+                    KeyCode::Char('k') if control => {
+                        program.open_prompt(Prompt::Condition);
+                    }
+                    KeyCode::Char('w') if control => {
+                        program.open_prompt(Prompt::Watch);
+                    }
+                    KeyCode::Char('d') if control => {
+                        program.cycle_cell_display();
+                    }
+                    KeyCode::Char('f') if control => {
+                        program.toggle_profile();
+                    }
+                    KeyCode::Char('i') if alt => {
+                        program.open_prompt(Prompt::InputFile);
+                    }
+                    KeyCode::Char('s') if alt => {
+                        program.open_prompt(Prompt::OutputFile);
+                    }
+                    KeyCode::Char('h') if alt => {
+                        program.toggle_output_hex();
+                    }
+                    KeyCode::Char('f') if alt => {
+                        program.open_prompt(Prompt::Search);
+                    }
+                    KeyCode::Char('n') if alt => {
+                        program.search_next(false);
+                    }
+                    KeyCode::Char('p') if alt => {
+                        program.search_next(true);
+                    }
+                    KeyCode::Char('r') if alt => {
+                        program.open_prompt(Prompt::Replace);
+                    }
+                    KeyCode::Char('m') if alt => {
+                        program.jump_to_matching_bracket();
+                    }
+                    // the `<` and `>` keys, as the input thread turns alt+arrow into ctrl+arrow
+                    KeyCode::Char(',') if alt => program.scroll_tape(-1),
+                    KeyCode::Char('.') if alt => program.scroll_tape(1),
+                    KeyCode::Char('y') if control => {
+                        program.open_prompt(Prompt::CellValue);
+                    }
+                    KeyCode::Char('a') if control => {
+                        program.move_pointer_to_view();
+                    }
+                    KeyCode::Char('q') if control => {
+                        program.edit_input();
+                    }
+                    KeyCode::Char('n') if control => {
+                        program.open_prompt(Prompt::Cell);
+                    }
+                    KeyCode::Char('g') if control => {
+                        program.toggle_coverage();
+                    }
+                    KeyCode::Char('l') if control => {
+                        program.open_prompt(Prompt::CoverageFile);
+                    }
+                    KeyCode::Char('o') if control => {
+                        program.step_over();
+                    }
+                    KeyCode::Char('o') if alt => {
+                        program.undo_step_over();
+                    }
+                    KeyCode::Char('u') if control => {
+                        program.step_out();
+                    }
+                    KeyCode::Char('u') if alt => {
+                        program.undo_step_out();
+                    }
+                    KeyCode::Char('t') if control => {
+                        program.run_to_cursor();
+                    }
+                    KeyCode::Char('t') if alt => {
+                        program.undo_to_cursor();
+                    }
                     KeyCode::Char('c') if control => {
                         program.editor.copy_selection();
                     }
@@ -91,6 +204,14 @@ pub fn spawn_program_thread(
                         program.editor.paste();
                         program.index_instructions();
                     }
+                    KeyCode::Char('z') if control => {
+                        program.editor.undo();
+                        program.index_instructions();
+                    }
+                    KeyCode::Char('z') if alt => {
+                        program.editor.redo();
+                        program.index_instructions();
+                    }
                     KeyCode::Char(c) => {
                         program.editor.insert_char(c);
                         program.index_instructions();
@@ -112,7 +233,7 @@ pub fn spawn_program_thread(
                     }
                     KeyCode::Up => program.editor.move_cursor(editor::CursorMove::Up, shift),
                     KeyCode::Down if control => {
-                        program.step_until_exception();
+                        program.start_running();
                     }
                     KeyCode::Down => program.editor.move_cursor(editor::CursorMove::Down, shift),
                     KeyCode::Left if control => {
@@ -122,6 +243,10 @@ pub fn spawn_program_thread(
                     KeyCode::Right if control => {
                         program.step().ok();
                     }
+                    KeyCode::PageUp => program.page_tape(false),
+                    KeyCode::PageDown => program.page_tape(true),
+                    KeyCode::Home => program.follow_tape_pointer(),
+                    KeyCode::F(1) => program.next_help_page(),
                     KeyCode::Right => program.editor.move_cursor(editor::CursorMove::Right, shift),
                     KeyCode::Tab => {
                         program.editor.indent();
@@ -133,6 +258,9 @@ pub fn spawn_program_thread(
                     _ => {}
                 },
                 Mode::Input => match event.code {
+                    KeyCode::Char('v') if control => {
+                        program.paste_input();
+                    }
                     KeyCode::Char(c) => {
                         program.add_input(c);
                     }
@@ -143,9 +271,95 @@ pub fn spawn_program_thread(
                             program.exit_input_mode(true);
                         }
                     }
+                    KeyCode::Backspace => {
+                        program.remove_input();
+                    }
+                    KeyCode::Esc => {
+                        program.exit_input_mode(false);
+                    }
+                    _ => {}
+                },
+                Mode::Prompt(Prompt::ConfirmReplace) => match event.code {
+                    KeyCode::Char('y') | KeyCode::Enter => {
+                        program.replace_match();
+                    }
+                    KeyCode::Char('n') => {
+                        program.skip_match();
+                    }
+                    KeyCode::Char('a') => {
+                        program.replace_all();
+                    }
+                    KeyCode::Char('q') | KeyCode::Esc => {
+                        program.cancel_prompt();
+                    }
+                    _ => {}
+                },
+                // search prompts take these keys on top of the ones every prompt takes
+                Mode::Prompt(Prompt::Search | Prompt::InstructionSearch)
+                    if matches!(event.code, KeyCode::Tab | KeyCode::Up | KeyCode::Down) =>
+                {
+                    match event.code {
+                        KeyCode::Tab => {
+                            program.toggle_instruction_search();
+                        }
+                        KeyCode::Up => {
+                            program.search_next(true);
+                        }
+                        _ => {
+                            program.search_next(false);
+                        }
+                    }
+                }
+                Mode::Prompt(_) => match event.code {
+                    KeyCode::Char(c) => {
+                        program.type_prompt(c);
+                    }
+                    KeyCode::Backspace => {
+                        program.erase_prompt();
+                    }
+                    KeyCode::Enter => {
+                        program.submit_prompt();
+                    }
+                    KeyCode::Esc => {
+                        program.cancel_prompt();
+                    }
+                    _ => {}
+                },
+                Mode::Playing => match event.code {
+                    KeyCode::Char('p') if control => {
+                        program.stop_playing();
+                    }
+                    KeyCode::Char(' ') | KeyCode::Esc => {
+                        program.stop_playing();
+                    }
+                    KeyCode::Up | KeyCode::Char('+') => {
+                        program.change_play_speed(true);
+                    }
+                    KeyCode::Down | KeyCode::Char('-') => {
+                        program.change_play_speed(false);
+                    }
+                    KeyCode::Left => {
+                        program.play_backwards = true;
+                    }
+                    KeyCode::Right => {
+                        program.play_backwards = false;
+                    }
+                    _ => {}
+                },
+                Mode::Running => match event.code {
+                    KeyCode::Char('c') if control => {
+                        program.stop_running();
+                    }
+                    KeyCode::Esc => {
+                        program.stop_running();
+                    }
                     _ => {}
                 },
             };
+
+            if program.is_running_mode() && !was_running {
+                tx_runner.send(()).unwrap();
+            }
         }
     });
 }
@@ -159,9 +373,13 @@ pub fn ui_loop(shared_state: SharedState, rx_ui: Receiver<()>) -> Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
+    let mut last_tick = Instant::now();
+
     loop {
         terminal.draw(|frame| {
             let mut guard = shared_state.lock().unwrap();
+            guard.play(last_tick.elapsed());
+            last_tick = Instant::now();
             ui::draw(&mut guard, frame);
             drop(guard);
         })?;
@@ -0,0 +1,220 @@
+use crate::config::Config;
+use crate::lint::Format;
+
+pub const USAGE: &str = "\
+usage: plaque [OPTIONS] [FILE]      open FILE in the debugger
+       plaque run [OPTIONS] FILE    run FILE to completion without the debugger
+       plaque profile [OPTIONS] FILE
+                                    run FILE, then report its hottest loops on stderr
+       plaque coverage [OPTIONS] FILE
+                                    run FILE once per --input (or on stdin), then write
+                                    lcov coverage of every run on stdout
+       plaque lint [--format text|json] FILE
+                                    check FILE for likely mistakes without running it,
+                                    exiting with status 3 if there are any
+
+options:
+  --cell-width 8|16|32              bits per tape cell (default 8)
+  --overflow wrap|saturate|error    cell overflow behaviour (default wrap)
+  --eof zero|max|unchanged|ask      what ',' does at the end of input (default zero)
+  --tape unbounded|infinite|fixed|wrap
+                                    how the tape behaves at its ends (default unbounded)
+  --tape-size N                     number of cells in a fixed or wrap tape (default 30000)
+  --tape-start N                    cell the tape pointer starts at (default 0)
+  --max-cells N                     limit on how far an unbounded or infinite tape grows
+  --input FILE                      read input from FILE instead of stdin, which coverage
+                                    takes more than once for more runs";
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Command {
+    Edit,
+    Run,
+    Profile,
+    Coverage,
+    Lint,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Args {
+    pub command: Command,
+    pub filepath: Option<String>,
+    pub config: Config,
+    /// Input files, which only coverage takes more than one of
+    pub inputs: Vec<String>,
+    /// How lint writes its findings
+    pub format: Format,
+}
+
+impl Args {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+        let mut args = args.into_iter().peekable();
+
+        let command = match args.peek().map(String::as_str) {
+            Some("run") => {
+                args.next();
+                Command::Run
+            }
+            Some("profile") => {
+                args.next();
+                Command::Profile
+            }
+            Some("coverage") => {
+                args.next();
+                Command::Coverage
+            }
+            Some("lint") => {
+                args.next();
+                Command::Lint
+            }
+            _ => Command::Edit,
+        };
+
+        let mut filepath = None;
+        let mut config = Config::default();
+        let mut inputs = vec![];
+        let mut format = None;
+        while let Some(arg) = args.next() {
+            if let Some(option) = arg.strip_prefix("--") {
+                let (name, value) = match option.split_once('=') {
+                    Some((name, value)) => (name.to_string(), value.to_string()),
+                    None => {
+                        let value = args
+                            .next()
+                            .ok_or_else(|| format!("missing value for '{arg}'"))?;
+                        (option.to_string(), value)
+                    }
+                };
+                match name.as_str() {
+                    "cell-width" => config.cell_width = value.parse()?,
+                    "overflow" => config.overflow = value.parse()?,
+                    "eof" => config.eof = value.parse()?,
+                    "tape" => config.tape = value.parse()?,
+                    "tape-size" => config.tape_size = parse_count(&name, &value)?,
+                    "tape-start" => config.tape_start = parse_count(&name, &value)?,
+                    "max-cells" => config.max_cells = Some(parse_count(&name, &value)?),
+                    "input" => inputs.push(value),
+                    "format" => format = Some(value.parse()?),
+                    _ => return Err(format!("unknown option '--{name}'")),
+                }
+                continue;
+            } else if arg.starts_with('-') && arg != "-" {
+                return Err(format!("unknown option '{arg}'"));
+            } else if filepath.is_some() {
+                return Err(format!("unexpected argument '{arg}'"));
+            }
+            filepath = Some(arg);
+        }
+
+        if command != Command::Edit && filepath.is_none() {
+            return Err("missing program file to run".to_string());
+        }
+        if command != Command::Coverage && inputs.len() > 1 {
+            return Err("only coverage takes more than one input file".to_string());
+        }
+        if command == Command::Lint && !inputs.is_empty() {
+            return Err("lint doesn't take input files".to_string());
+        }
+        if command != Command::Lint && format.is_some() {
+            return Err("only lint takes a format".to_string());
+        }
+        config.validate()?;
+
+        Ok(Args {
+            command,
+            filepath,
+            config,
+            inputs,
+            format: format.unwrap_or_default(),
+        })
+    }
+}
+
+fn parse_count(name: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {name} '{value}' (expected a number)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CellWidth, Eof, Overflow, TapeMode};
+
+    fn parse(line: &str) -> Result<Args, String> {
+        Args::parse(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn editor_is_the_default_command() {
+        let args = parse("").unwrap();
+        assert_eq!(args.command, Command::Edit);
+        assert_eq!(args.filepath, None);
+
+        let args = parse("hello.bf").unwrap();
+        assert_eq!(args.command, Command::Edit);
+        assert_eq!(args.filepath, Some("hello.bf".to_string()));
+    }
+
+    #[test]
+    fn run_requires_a_file() {
+        let args = parse("run hello.bf").unwrap();
+        assert_eq!(args.command, Command::Run);
+        assert_eq!(args.filepath, Some("hello.bf".to_string()));
+
+        assert!(parse("run").is_err());
+
+        let args = parse("profile --eof max hello.bf").unwrap();
+        assert_eq!(args.command, Command::Profile);
+        assert!(parse("profile").is_err());
+    }
+
+    #[test]
+    fn takes_input_files() {
+        let args = parse("coverage hello.bf --input a.txt --input=b.txt").unwrap();
+        assert_eq!(args.command, Command::Coverage);
+        assert_eq!(args.inputs, vec!["a.txt".to_string(), "b.txt".to_string()]);
+
+        let args = parse("run hello.bf --input a.txt").unwrap();
+        assert_eq!(args.inputs, vec!["a.txt".to_string()]);
+        assert!(parse("hello.bf --input a.txt --input b.txt").is_err());
+    }
+
+    #[test]
+    fn lint_takes_a_format() {
+        let args = parse("lint hello.bf").unwrap();
+        assert_eq!(args.command, Command::Lint);
+        assert_eq!(args.format, Format::Text);
+
+        let args = parse("lint --format json hello.bf").unwrap();
+        assert_eq!(args.format, Format::Json);
+
+        assert!(parse("lint --format yaml hello.bf").is_err());
+        assert!(parse("lint hello.bf --input a.txt").is_err());
+        assert!(parse("run --format json hello.bf").is_err());
+    }
+
+    #[test]
+    fn parses_engine_options() {
+        let args = parse("run --cell-width 16 hello.bf --overflow=error --eof ask").unwrap();
+        assert_eq!(args.filepath, Some("hello.bf".to_string()));
+        assert_eq!(args.config.cell_width, CellWidth::U16);
+        assert_eq!(args.config.overflow, Overflow::Error);
+        assert_eq!(args.config.eof, Eof::Ask);
+
+        let args = parse("--tape=wrap --tape-size 100 --tape-start 50").unwrap();
+        assert_eq!(args.config.tape, TapeMode::Wrapping);
+        assert_eq!(args.config.tape_size, 100);
+        assert_eq!(args.config.tape_start, 50);
+
+        assert!(parse("--tape fixed --tape-size 10 --tape-start 10").is_err());
+        assert!(parse("--max-cells lots").is_err());
+        assert!(parse("--cell-width 12 hello.bf").is_err());
+        assert!(parse("hello.bf --overflow").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_arguments() {
+        assert!(parse("--nope hello.bf").is_err());
+        assert!(parse("run hello.bf extra.bf").is_err());
+    }
+}
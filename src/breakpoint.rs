@@ -0,0 +1,262 @@
+use crate::engine::Engine;
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Operand {
+    /// The cell under the tape pointer
+    CurrentCell,
+    /// The cell at a (possibly negative) index
+    Cell(isize),
+    TapePointer,
+    OutputLength,
+    InputLength,
+    Steps,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Clause {
+    pub operand: Operand,
+    pub comparison: Comparison,
+    pub value: i64,
+}
+
+/// A condition on the engine state made of clauses joined by `&&`, e.g. `cell[3] == 10 && steps > 100`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Condition {
+    pub text: String,
+    pub clauses: Vec<Clause>,
+}
+
+impl Operand {
+    fn value(self, engine: &Engine) -> i64 {
+        match self {
+            Operand::CurrentCell => engine.cell() as i64,
+            Operand::Cell(index) => engine.cell_at(index).unwrap_or(0) as i64,
+            Operand::TapePointer => engine.cell_index(engine.tape_pointer) as i64,
+            Operand::OutputLength => engine.output.len() as i64,
+            Operand::InputLength => engine.input.len() as i64,
+            Operand::Steps => engine.history.len as i64,
+        }
+    }
+}
+
+impl FromStr for Operand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Operand, String> {
+        let name: String = s
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '_')
+            .collect::<String>()
+            .to_lowercase();
+
+        match name.as_str() {
+            "cell" => Ok(Operand::CurrentCell),
+            "pointer" | "tapepointer" => Ok(Operand::TapePointer),
+            "outputlength" | "outputlen" => Ok(Operand::OutputLength),
+            "inputlength" | "inputlen" => Ok(Operand::InputLength),
+            "steps" => Ok(Operand::Steps),
+            _ => name
+                .strip_prefix("cell[")
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|index| index.parse().ok())
+                .map(Operand::Cell)
+                .ok_or_else(|| format!("unknown value '{}'", s.trim())),
+        }
+    }
+}
+
+impl Comparison {
+    // two character operators come first so that "<=" isn't read as "<"
+    const SYMBOLS: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    fn compare(self, left: i64, right: i64) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+impl FromStr for Clause {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Clause, String> {
+        let (symbol, comparison, at) = Comparison::SYMBOLS
+            .iter()
+            .find_map(|&(symbol, comparison)| s.find(symbol).map(|at| (symbol, comparison, at)))
+            .ok_or_else(|| format!("missing comparison in '{}'", s.trim()))?;
+
+        let operand = s[..at].parse()?;
+        let value = s[at + symbol.len()..].trim();
+        let value = value
+            .parse()
+            .map_err(|_| format!("expected a number but found '{value}'"))?;
+
+        Ok(Clause {
+            operand,
+            comparison,
+            value,
+        })
+    }
+}
+
+impl Condition {
+    pub fn holds(&self, engine: &Engine) -> bool {
+        self.clauses.iter().all(|clause| {
+            clause
+                .comparison
+                .compare(clause.operand.value(engine), clause.value)
+        })
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Condition, String> {
+        let clauses = s
+            .split("&&")
+            .map(str::parse)
+            .collect::<Result<Vec<Clause>, String>>()?;
+
+        Ok(Condition {
+            text: s.trim().to_string(),
+            clauses,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WatchKind {
+    /// Break whenever the cell is written, even with the same value
+    Write,
+    /// Break only when the value of the cell changes
+    Change,
+}
+
+/// Breaks execution when a tape cell is written to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Watchpoint {
+    pub cell: isize,
+    pub kind: WatchKind,
+}
+
+impl FromStr for Watchpoint {
+    type Err = String;
+
+    /// Parses `<cell> [write|change]`, watching for changes by default
+    fn from_str(s: &str) -> Result<Watchpoint, String> {
+        let mut words = s.split_whitespace();
+        let cell = words.next().ok_or("missing cell to watch")?;
+        let cell = cell
+            .trim_start_matches("cell[")
+            .trim_end_matches(']')
+            .parse()
+            .map_err(|_| format!("expected a cell number but found '{cell}'"))?;
+
+        let kind = match words.next() {
+            None | Some("change") => WatchKind::Change,
+            Some("write") => WatchKind::Write,
+            Some(kind) => return Err(format!("unknown watch '{kind}' (expected write or change)")),
+        };
+
+        Ok(Watchpoint { cell, kind })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::Instruction::*;
+
+    #[test]
+    fn parses_conditions() {
+        let condition: Condition = "cell[3] == 10 && tape_pointer>100".parse().unwrap();
+        assert_eq!(
+            condition.clauses,
+            vec![
+                Clause {
+                    operand: Operand::Cell(3),
+                    comparison: Comparison::Equal,
+                    value: 10
+                },
+                Clause {
+                    operand: Operand::TapePointer,
+                    comparison: Comparison::Greater,
+                    value: 100
+                }
+            ]
+        );
+
+        let clause: Clause = "Output Length >= 5".parse().unwrap();
+        assert_eq!(clause.operand, Operand::OutputLength);
+        assert_eq!(clause.comparison, Comparison::GreaterOrEqual);
+
+        let clause: Clause = "cell[-2] <= -1".parse().unwrap();
+        assert_eq!(clause.operand, Operand::Cell(-2));
+        assert_eq!(clause.value, -1);
+
+        assert!("cell[3]".parse::<Condition>().is_err());
+        assert!("cells == 3".parse::<Condition>().is_err());
+        assert!("cell == three".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn evaluates_conditions() {
+        let mut engine = Engine::new(vec![Increment, IncrementPointer, Increment, Output]);
+        engine.next_instruction().unwrap();
+        for _ in 0..3 {
+            engine.step().unwrap();
+        }
+
+        let holds = |text: &str| text.parse::<Condition>().unwrap().holds(&engine);
+        assert!(holds("cell[0] == 1 && cell[1] == 1"));
+        assert!(holds("pointer == 1 && steps == 3"));
+        assert!(holds("cell[5] == 0"));
+        assert!(!holds("output_len > 0"));
+    }
+
+    #[test]
+    fn parses_watchpoints() {
+        let watchpoint: Watchpoint = "12".parse().unwrap();
+        assert_eq!(watchpoint.cell, 12);
+        assert_eq!(watchpoint.kind, WatchKind::Change);
+
+        let watchpoint: Watchpoint = "cell[-1] write".parse().unwrap();
+        assert_eq!(watchpoint.cell, -1);
+        assert_eq!(watchpoint.kind, WatchKind::Write);
+
+        assert!("".parse::<Watchpoint>().is_err());
+        assert!("3 read".parse::<Watchpoint>().is_err());
+    }
+}
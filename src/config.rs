@@ -0,0 +1,206 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CellWidth {
+    #[default]
+    U8,
+    U16,
+    U32,
+}
+
+impl CellWidth {
+    pub fn max(self) -> u32 {
+        match self {
+            CellWidth::U8 => u8::MAX as u32,
+            CellWidth::U16 => u16::MAX as u32,
+            CellWidth::U32 => u32::MAX,
+        }
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            CellWidth::U8 => 8,
+            CellWidth::U16 => 16,
+            CellWidth::U32 => 32,
+        }
+    }
+
+    /// Number of decimal digits needed to display the largest cell value
+    pub fn digits(self) -> usize {
+        self.max().ilog10() as usize + 1
+    }
+}
+
+impl FromStr for CellWidth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CellWidth, String> {
+        match s {
+            "8" => Ok(CellWidth::U8),
+            "16" => Ok(CellWidth::U16),
+            "32" => Ok(CellWidth::U32),
+            _ => Err(format!("invalid cell width '{s}' (expected 8, 16 or 32)")),
+        }
+    }
+}
+
+/// What happens when a cell is incremented past its maximum or decremented below zero
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Overflow {
+    #[default]
+    Wrap,
+    Saturate,
+    Error,
+}
+
+impl FromStr for Overflow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Overflow, String> {
+        match s {
+            "wrap" => Ok(Overflow::Wrap),
+            "saturate" => Ok(Overflow::Saturate),
+            "error" => Ok(Overflow::Error),
+            _ => Err(format!(
+                "invalid overflow '{s}' (expected wrap, saturate or error)"
+            )),
+        }
+    }
+}
+
+/// What `,` does when there is no input left
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Eof {
+    #[default]
+    Zero,
+    /// Set the cell to its maximum value, i.e. -1
+    Max,
+    Unchanged,
+    /// Pause and ask for more input
+    Ask,
+}
+
+impl Eof {
+    pub fn next(self) -> Eof {
+        match self {
+            Eof::Zero => Eof::Max,
+            Eof::Max => Eof::Unchanged,
+            Eof::Unchanged => Eof::Ask,
+            Eof::Ask => Eof::Zero,
+        }
+    }
+}
+
+impl fmt::Display for Eof {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Eof::Zero => "zero",
+            Eof::Max => "max",
+            Eof::Unchanged => "unchanged",
+            Eof::Ask => "ask",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Eof {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Eof, String> {
+        match s {
+            "zero" => Ok(Eof::Zero),
+            "max" => Ok(Eof::Max),
+            "unchanged" => Ok(Eof::Unchanged),
+            "ask" => Ok(Eof::Ask),
+            _ => Err(format!(
+                "invalid eof '{s}' (expected zero, max, unchanged or ask)"
+            )),
+        }
+    }
+}
+
+/// How the tape behaves at its ends
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TapeMode {
+    /// Starts at cell 0 and grows to the right as needed
+    #[default]
+    Unbounded,
+    /// Grows in both directions, with negative cell indices to the left of the start
+    Infinite,
+    /// A fixed number of cells, moving off either end is an error
+    Fixed,
+    /// A fixed number of cells, moving off either end wraps around to the other
+    Wrapping,
+}
+
+impl TapeMode {
+    pub fn is_fixed_size(self) -> bool {
+        matches!(self, TapeMode::Fixed | TapeMode::Wrapping)
+    }
+}
+
+impl FromStr for TapeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TapeMode, String> {
+        match s {
+            "unbounded" => Ok(TapeMode::Unbounded),
+            "infinite" => Ok(TapeMode::Infinite),
+            "fixed" => Ok(TapeMode::Fixed),
+            "wrap" => Ok(TapeMode::Wrapping),
+            _ => Err(format!(
+                "invalid tape '{s}' (expected unbounded, infinite, fixed or wrap)"
+            )),
+        }
+    }
+}
+
+/// Interpreter semantics that vary between Brainfuck implementations.
+/// Output always writes the low byte of the current cell.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Config {
+    pub cell_width: CellWidth,
+    pub overflow: Overflow,
+    pub eof: Eof,
+    pub tape: TapeMode,
+    /// Number of cells in a fixed size tape
+    pub tape_size: usize,
+    /// Cell the tape pointer starts at
+    pub tape_start: usize,
+    /// Largest number of cells a growing tape may allocate
+    pub max_cells: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            cell_width: CellWidth::default(),
+            overflow: Overflow::default(),
+            eof: Eof::default(),
+            tape: TapeMode::default(),
+            tape_size: 30_000,
+            tape_start: 0,
+            max_cells: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.tape.is_fixed_size() && self.tape_start >= self.tape_size {
+            Err(format!(
+                "tape start {} is outside a tape of {} cells",
+                self.tape_start, self.tape_size
+            ))
+        } else if self.max_cells.is_some_and(|max| self.tape_start >= max) {
+            Err(format!(
+                "tape start {} is outside the limit of {} cells",
+                self.tape_start,
+                self.max_cells.unwrap_or_default()
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::instruction::Instruction;
+use crate::program::Program;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Which ways an instruction has gone, across every run since the program was loaded
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Coverage {
+    /// Carried on to the next instruction
+    pub continued: bool,
+    /// Moved to the matching jump
+    pub jumped: bool,
+}
+
+impl Coverage {
+    pub fn executed(self) -> bool {
+        self.continued || self.jumped
+    }
+}
+
+/// Coverage in the lcov tracefile format, with a line record for every line that has
+/// instructions and a pair of branch records for every jump
+pub fn lcov(program: &Program) -> String {
+    let engine = &program.engine;
+    let source = program
+        .editor
+        .filepath
+        .as_ref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_default();
+
+    let mut lines = BTreeMap::new();
+    for (i, &(line, _)) in program.instruction_positions.iter().enumerate() {
+        *lines.entry(line).or_insert(false) |= engine.coverage[i].executed();
+    }
+
+    let mut report = format!("TN:\nSF:{source}\n");
+    let (mut found, mut hit) = (0, 0);
+    for (i, instruction) in engine.instructions.iter().enumerate() {
+        if !matches!(
+            instruction,
+            Instruction::JumpForward | Instruction::JumpBackward
+        ) {
+            continue;
+        }
+        let coverage = engine.coverage[i];
+        let line = program.instruction_positions[i].0 + 1;
+        // a `[` continues into its body, while a `]` jumps back into it
+        let (into_body, past_body) = match instruction {
+            Instruction::JumpForward => (coverage.continued, coverage.jumped),
+            _ => (coverage.jumped, coverage.continued),
+        };
+        for (branch, taken) in [into_body, past_body].into_iter().enumerate() {
+            let taken = match (coverage.executed(), taken) {
+                (false, _) => "-",
+                (true, true) => "1",
+                (true, false) => "0",
+            };
+            writeln!(report, "BRDA:{line},{i},{branch},{taken}").unwrap();
+            found += 1;
+            hit += (taken == "1") as usize;
+        }
+    }
+    if found > 0 {
+        writeln!(report, "BRF:{found}\nBRH:{hit}").unwrap();
+    }
+
+    for (&line, &executed) in &lines {
+        writeln!(report, "DA:{},{}", line + 1, executed as u8).unwrap();
+    }
+    let lines_hit = lines.values().filter(|&&executed| executed).count();
+    writeln!(report, "LF:{}\nLH:{lines_hit}\nend_of_record", lines.len()).unwrap();
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless;
+
+    fn run(program: &mut Program, input: &[u8]) {
+        program.reset();
+        program.engine.input = input.to_vec();
+        headless::execute(program, &mut &[][..], &mut vec![]).unwrap();
+    }
+
+    #[test]
+    fn accumulates_across_runs() {
+        let mut program = Program::new();
+        program.load_source(",[\n>+<-\n]\n>[\n-\n]");
+
+        run(&mut program, b"\x00");
+        let report = lcov(&program);
+        assert!(report.contains("DA:2,0\n"));
+        assert!(report.contains("BRDA:1,1,0,0\nBRDA:1,1,1,1\n"));
+        assert!(report.contains("BRDA:4,8,0,0\n"));
+        assert!(report.contains("LF:6\nLH:4\n"));
+
+        run(&mut program, b"\x01");
+        let report = lcov(&program);
+        assert!(report.contains("DA:2,1\n"));
+        assert!(report.contains("BRDA:1,1,0,1\nBRDA:1,1,1,1\n"));
+        assert!(report.contains("BRDA:3,6,0,0\nBRDA:3,6,1,1\n"));
+        assert!(report.contains("LF:6\nLH:6\n"));
+        assert!(report.ends_with("end_of_record\n"));
+    }
+}
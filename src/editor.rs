@@ -1,4 +1,10 @@
+use std::collections::VecDeque;
+use std::ops::Range;
 use std::path::PathBuf;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Most text edits kept for undo, oldest dropped first
+const EDIT_HISTORY_LIMIT: usize = 1000;
 
 #[derive(Debug)]
 pub struct Editor {
@@ -11,6 +17,43 @@ pub struct Editor {
     pub window_top_line: usize,
     pub pinned_col: usize,
     pub dirty: bool,
+    undo_stack: VecDeque<Change>,
+    redo_stack: Vec<Change>,
+    /// The kind of the latest edit and where it left the cursor
+    last_edit: Option<(EditKind, (usize, usize))>,
+}
+
+/// The rows an edit replaced, with the cursor and selection either side of it
+#[derive(Clone, Debug)]
+struct Change {
+    /// First row of the edit, which `old` and `new` both start at
+    row: usize,
+    old: Vec<String>,
+    new: Vec<String>,
+    before: CursorState,
+    after: CursorState,
+}
+
+type CursorState = ((usize, usize), Option<(usize, usize)>);
+
+/// Consecutive edits of the same kind that undo as one step
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum EditKind {
+    Typing,
+    Other,
+}
+
+/// Columns count graphemes, so an accented letter or an emoji is a single column
+/// however many chars or bytes it takes
+pub fn columns(line: &str) -> usize {
+    line.graphemes(true).count()
+}
+
+/// Byte offset of the grapheme at a column, or the end of the line for the column past it
+pub fn byte_offset(line: &str, col: usize) -> usize {
+    line.grapheme_indices(true)
+        .nth(col)
+        .map_or(line.len(), |(offset, _)| offset)
 }
 
 #[derive(Debug)]
@@ -33,11 +76,14 @@ impl Editor {
             window_top_line: 0,
             pinned_col: 0,
             dirty: false,
+            undo_stack: VecDeque::new(),
+            redo_stack: vec![],
+            last_edit: None,
         }
     }
 
-    pub fn line_chars(&self, i: usize) -> usize {
-        self.lines[i].chars().count()
+    pub fn line_len(&self, i: usize) -> usize {
+        columns(&self.lines[i])
     }
 
     pub fn save(&mut self) -> std::io::Result<()> {
@@ -56,64 +102,182 @@ impl Editor {
         Ok(())
     }
 
-    pub fn insert_char(&mut self, c: char) {
-        self.delete_selection();
+    /// Rows an edit at the cursor can touch: those the cursor and selection span, and one
+    /// either side for joining lines
+    fn rows_around_cursor(&self) -> Range<usize> {
+        let (row, _) = self.cursor;
+        let (first, last) = match self.selection {
+            Some((selection_row, _)) => (row.min(selection_row), row.max(selection_row)),
+            None => (row, row),
+        };
+        first.saturating_sub(1)..std::cmp::min(last + 2, self.lines.len())
+    }
 
-        let (row, col) = self.cursor;
+    fn push_undo(&mut self, change: Change) {
+        if self.undo_stack.len() == EDIT_HISTORY_LIMIT {
+            self.undo_stack.pop_front();
+        }
+        self.undo_stack.push_back(change);
+    }
+
+    /// Put back one side of a change, with `from` being the rows it replaces
+    fn apply(&mut self, row: usize, from: usize, lines: &[String], state: CursorState) {
+        self.lines.splice(row..row + from, lines.iter().cloned());
+        let ((cursor_row, cursor_col), selection) = state;
+        self.set_pinned_cursor(cursor_row, cursor_col);
+        self.selection = selection;
+        self.dirty = true;
+        self.scroll_into_view();
+    }
 
-        self.lines[row].insert(col, c);
-        self.set_pinned_cursor(row, col + 1);
+    /// Apply an edit that only changes `rows`, though it may add or remove rows among them,
+    /// recording those rows unless it continues a run of typing
+    fn edit(&mut self, kind: EditKind, rows: Range<usize>, f: impl FnOnce(&mut Editor)) {
+        let before = (self.cursor, self.selection);
+        let (len, old) = (self.lines.len(), self.lines[rows.clone()].to_vec());
+        f(self);
+
+        let new_end = rows.end + self.lines.len() - len;
+        if self.lines[rows.start..new_end] == old[..] {
+            return;
+        }
+
+        let after = (self.cursor, self.selection);
+        let continues_typing = kind == EditKind::Typing
+            && before.1.is_none()
+            && self.last_edit == Some((kind, before.0));
+        match self.undo_stack.back_mut() {
+            // typing stays on one row, which the last change already covers
+            Some(change) if continues_typing => {
+                let row = before.0 .0;
+                change.new[row - change.row] = self.lines[row].clone();
+                change.after = after;
+            }
+            _ => self.push_undo(Change {
+                row: rows.start,
+                old,
+                new: self.lines[rows.start..new_end].to_vec(),
+                before,
+                after,
+            }),
+        }
+        self.redo_stack.clear();
+        self.last_edit = Some((kind, self.cursor));
         self.dirty = true;
     }
 
+    /// Revert the last edit, returning whether there was one
+    pub fn undo(&mut self) -> bool {
+        let Some(change) = self.undo_stack.pop_back() else {
+            return false;
+        };
+        self.apply(change.row, change.new.len(), &change.old, change.before);
+        self.redo_stack.push(change);
+        self.last_edit = None;
+        true
+    }
+
+    /// Reapply the last undone edit, returning whether there was one
+    pub fn redo(&mut self) -> bool {
+        let Some(change) = self.redo_stack.pop() else {
+            return false;
+        };
+        self.apply(change.row, change.old.len(), &change.new, change.after);
+        self.push_undo(change);
+        self.last_edit = None;
+        true
+    }
+
+    /// Forget edit history, such as when the text is replaced wholesale
+    pub fn clear_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.last_edit = None;
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        self.edit(EditKind::Typing, self.rows_around_cursor(), |editor| {
+            editor.remove_selection();
+
+            let (row, col) = editor.cursor;
+            let offset = byte_offset(&editor.lines[row], col);
+
+            // a combining mark joins the grapheme before it rather than adding a column
+            editor.lines[row].insert(offset, c);
+            let col = columns(&editor.lines[row][..offset + c.len_utf8()]);
+            editor.set_pinned_cursor(row, col);
+        });
+    }
+
     pub fn newline(&mut self) {
-        self.delete_selection();
+        self.edit(
+            EditKind::Other,
+            self.rows_around_cursor(),
+            Editor::split_line,
+        );
+    }
+
+    fn split_line(&mut self) {
+        self.remove_selection();
 
         let (row, col) = self.cursor;
-        let line: String = self.lines[row].chars().skip(col).collect();
+        let offset = byte_offset(&self.lines[row], col);
+        let line = self.lines[row].split_off(offset);
 
-        self.lines[row].truncate(col);
         self.lines.insert(row + 1, line);
         self.set_pinned_cursor(row + 1, 0);
-        self.dirty = true;
         self.scroll_into_view();
     }
 
     pub fn backward_delete(&mut self) {
+        self.edit(
+            EditKind::Other,
+            self.rows_around_cursor(),
+            Editor::delete_before_cursor,
+        );
+    }
+
+    fn delete_before_cursor(&mut self) {
         if self.selection.is_some() {
-            return self.delete_selection();
+            return self.remove_selection();
         }
 
         let (row, col) = self.cursor;
         if col > 0 {
-            self.lines[row].remove(col - 1);
+            let line = &mut self.lines[row];
+            line.replace_range(byte_offset(line, col - 1)..byte_offset(line, col), "");
             self.set_pinned_cursor(row, col - 1);
-            self.dirty = true;
         } else if row > 0 {
             let line = self.lines[row].clone();
-            let prev_line_len = self.line_chars(row - 1);
+            let prev_line_len = self.line_len(row - 1);
 
             self.lines.remove(row);
             self.lines[row - 1] += &line;
             self.set_pinned_cursor(row - 1, prev_line_len);
-            self.dirty = true;
         }
         self.scroll_into_view();
     }
 
     pub fn forward_delete(&mut self) {
+        self.edit(
+            EditKind::Other,
+            self.rows_around_cursor(),
+            Editor::delete_after_cursor,
+        );
+    }
+
+    fn delete_after_cursor(&mut self) {
         if self.selection.is_some() {
-            return self.delete_selection();
+            return self.remove_selection();
         }
 
         let (row, col) = self.cursor;
-        if col < self.lines[row].len() {
-            self.lines[row].remove(col);
-            self.dirty = true;
+        if col < self.line_len(row) {
+            let line = &mut self.lines[row];
+            line.replace_range(byte_offset(line, col)..byte_offset(line, col + 1), "");
         } else if row + 1 < self.lines.len() {
             let next_line = self.lines.remove(row + 1);
             self.lines[row] += &next_line;
-            self.dirty = true;
         }
         self.scroll_into_view();
     }
@@ -126,6 +290,7 @@ impl Editor {
     pub fn move_cursor(&mut self, cursor_move: CursorMove, selection: bool) {
         let (row, col) = self.cursor;
 
+        self.last_edit = None;
         self.selection = if selection {
             Some(self.selection.unwrap_or(self.cursor))
         } else {
@@ -138,7 +303,7 @@ impl Editor {
                     return;
                 }
                 let row = row - 1;
-                let clamped_col = std::cmp::min(self.pinned_col, self.line_chars(row));
+                let clamped_col = std::cmp::min(self.pinned_col, self.line_len(row));
                 self.cursor = (row, clamped_col);
                 self.scroll_into_view();
             }
@@ -147,7 +312,7 @@ impl Editor {
                     return;
                 }
                 let row = row + 1;
-                let clamped_col = std::cmp::min(self.pinned_col, self.line_chars(row));
+                let clamped_col = std::cmp::min(self.pinned_col, self.line_len(row));
                 self.cursor = (row, clamped_col);
                 self.scroll_into_view();
             }
@@ -155,11 +320,11 @@ impl Editor {
                 if col > 0 {
                     self.set_pinned_cursor(row, col - 1);
                 } else if row > 0 {
-                    self.set_pinned_cursor(row - 1, self.line_chars(row - 1));
+                    self.set_pinned_cursor(row - 1, self.line_len(row - 1));
                 }
             }
             CursorMove::Right => {
-                if col < self.line_chars(row) {
+                if col < self.line_len(row) {
                     self.set_pinned_cursor(row, col + 1);
                 } else if row < self.lines.len() - 1 {
                     self.set_pinned_cursor(row + 1, 0);
@@ -169,6 +334,14 @@ impl Editor {
     }
 
     pub fn delete_selection(&mut self) {
+        self.edit(
+            EditKind::Other,
+            self.rows_around_cursor(),
+            Editor::remove_selection,
+        );
+    }
+
+    fn remove_selection(&mut self) {
         let (ci, cj) = self.cursor;
         let Some((si, sj)) = self.selection else {
             return;
@@ -182,8 +355,11 @@ impl Editor {
         let (xi, xj) = std::cmp::min((si, sj), (ci, cj));
         let (yi, yj) = std::cmp::max((si, sj), (ci, cj));
 
+        let xj = byte_offset(&self.lines[xi], xj);
+        let yj = byte_offset(&self.lines[yi], yj);
+
         if xi == yi {
-            self.lines[xi].drain(std::cmp::min(xj, yj)..std::cmp::max(xj, yj));
+            self.lines[xi].drain(xj..yj);
         } else {
             self.lines[xi].truncate(xj);
             self.lines[yi].drain(0..yj);
@@ -203,6 +379,8 @@ impl Editor {
 
         let (xi, xj) = std::cmp::min((si, sj), (ci, cj));
         let (yi, yj) = std::cmp::max((si, sj), (ci, cj));
+        let xj = byte_offset(&self.lines[xi], xj);
+        let yj = byte_offset(&self.lines[yi], yj);
 
         if xi == yi {
             let line = self.lines[xi][xj..yj].to_string();
@@ -222,10 +400,19 @@ impl Editor {
     }
 
     pub fn paste(&mut self) {
+        self.edit(
+            EditKind::Other,
+            self.rows_around_cursor(),
+            Editor::insert_clipboard,
+        );
+    }
+
+    fn insert_clipboard(&mut self) {
         let (i, j) = self.cursor;
         let Some(clipboard) = self.clipboard.clone() else {
             return;
         };
+        let j = byte_offset(&self.lines[i], j);
 
         if clipboard.len() == 1 {
             self.lines[i].insert_str(j, &clipboard[0]);
@@ -240,7 +427,43 @@ impl Editor {
         self.scroll_into_view();
     }
 
+    /// Replace each match, given as the cells it covers in order, with `text` as a single
+    /// edit. Anything between the cells of a match is kept, with the text going where the
+    /// first cell was
+    pub fn replace(&mut self, matches: &[Vec<(usize, usize)>], text: &str) {
+        let rows = matches.iter().flatten().map(|&(row, _)| row);
+        let rows = match (rows.clone().min(), rows.max()) {
+            (Some(first), Some(last)) => first..last + 1,
+            _ => return,
+        };
+        self.edit(EditKind::Other, rows, |editor| {
+            // work back from the end so earlier cells stay where they are
+            for cells in matches.iter().rev() {
+                for &(row, col) in cells.iter().rev() {
+                    let line = &mut editor.lines[row];
+                    line.replace_range(byte_offset(line, col)..byte_offset(line, col + 1), "");
+                }
+                if let Some(&(row, col)) = cells.first() {
+                    let offset = byte_offset(&editor.lines[row], col);
+                    editor.lines[row].insert_str(offset, text);
+                    let col = columns(&editor.lines[row][..offset + text.len()]);
+                    editor.selection = None;
+                    editor.set_pinned_cursor(row, col);
+                }
+            }
+        });
+        self.scroll_into_view();
+    }
+
     pub fn indent(&mut self) {
+        self.edit(
+            EditKind::Other,
+            self.rows_around_cursor(),
+            Editor::indent_lines,
+        );
+    }
+
+    fn indent_lines(&mut self) {
         let (si, sj) = self.selection.unwrap_or(self.cursor);
         let (ci, cj) = self.cursor;
         let xi = std::cmp::min(si, ci);
@@ -264,12 +487,10 @@ impl Editor {
         if !(ci > si && cj == 0) {
             self.cursor = (ci, cj + 2);
         }
-
-        self.dirty = true;
     }
 
     pub fn in_selection(&self, i: usize, j: usize) -> bool {
-        if self.lines[i].len() == j {
+        if self.line_len(i) == j {
             return false;
         }
 
@@ -299,3 +520,146 @@ impl Editor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(lines: &[&str]) -> Editor {
+        let mut editor = Editor::new();
+        editor.lines = lines.iter().map(|line| line.to_string()).collect();
+        editor
+    }
+
+    #[test]
+    fn undo_groups_typing() {
+        let mut editor = editor(&[""]);
+        for c in "+++".chars() {
+            editor.insert_char(c);
+        }
+        editor.newline();
+        editor.insert_char('-');
+        editor.insert_char('-');
+        assert_eq!(editor.lines, vec!["+++", "--"]);
+
+        assert!(editor.undo());
+        assert_eq!(editor.lines, vec!["+++", ""]);
+        assert_eq!(editor.cursor, (1, 0));
+        assert!(editor.undo());
+        assert!(editor.undo());
+        assert_eq!(editor.lines, vec![""]);
+        assert_eq!(editor.cursor, (0, 0));
+        assert!(!editor.undo());
+
+        assert!(editor.redo());
+        assert!(editor.redo());
+        assert_eq!(editor.lines, vec!["+++", ""]);
+        assert_eq!(editor.cursor, (1, 0));
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut editor = editor(&[""]);
+        for _ in 0..EDIT_HISTORY_LIMIT + 1 {
+            editor.newline();
+        }
+        while editor.undo() {}
+        assert_eq!(editor.lines.len(), 2);
+
+        // redoing everything fits back in the limit
+        while editor.redo() {}
+        assert_eq!(editor.undo_stack.len(), EDIT_HISTORY_LIMIT);
+        assert_eq!(editor.lines.len(), EDIT_HISTORY_LIMIT + 2);
+    }
+
+    #[test]
+    fn undo_keeps_only_the_changed_rows() {
+        let mut editor = editor(&["+", "-", ".", ","]);
+        editor.set_pinned_cursor(2, 1);
+        editor.insert_char('>');
+        editor.insert_char('<');
+        editor.backward_delete();
+        editor.move_cursor(CursorMove::Up, false);
+        editor.move_cursor(CursorMove::Left, false);
+        editor.backward_delete();
+        assert_eq!(editor.lines, vec!["+-", ".>", ","]);
+
+        let change = editor.undo_stack.back().unwrap();
+        assert_eq!((change.row, change.old.len(), change.new.len()), (0, 3, 2));
+        assert_eq!(editor.undo_stack[0].new, vec!["-", ".><", ","]);
+
+        editor.undo();
+        assert_eq!(editor.lines, vec!["+", "-", ".>", ","]);
+        editor.undo();
+        assert_eq!(editor.lines, vec!["+", "-", ".><", ","]);
+        editor.undo();
+        assert_eq!(editor.lines, vec!["+", "-", ".", ","]);
+        assert_eq!(editor.cursor, (2, 1));
+    }
+
+    #[test]
+    fn moving_the_cursor_ends_a_group() {
+        let mut editor = editor(&["><"]);
+        editor.set_pinned_cursor(0, 1);
+        editor.insert_char('+');
+        editor.move_cursor(CursorMove::Right, false);
+        editor.insert_char('-');
+        assert_eq!(editor.lines, vec![">+<-"]);
+
+        editor.undo();
+        assert_eq!(editor.lines, vec![">+<"]);
+        assert_eq!(editor.cursor, (0, 3));
+    }
+
+    #[test]
+    fn undo_restores_the_selection() {
+        let mut editor = editor(&["+[-]."]);
+        editor.move_cursor(CursorMove::Right, false);
+        for _ in 0..3 {
+            editor.move_cursor(CursorMove::Right, true);
+        }
+        editor.cut_selection();
+        assert_eq!(editor.lines, vec!["+."]);
+
+        // edits that change nothing aren't recorded, and new edits drop the redo history
+        editor.forward_delete();
+        editor.forward_delete();
+        editor.undo();
+        assert_eq!(editor.lines, vec!["+."]);
+        editor.undo();
+        assert_eq!(editor.lines, vec!["+[-]."]);
+        assert_eq!((editor.selection, editor.cursor), (Some((0, 1)), (0, 4)));
+
+        editor.paste();
+        assert!(!editor.redo());
+    }
+
+    #[test]
+    fn edits_by_grapheme() {
+        let mut editor = editor(&["caf\u{E9} \u{1F600}+"]);
+        assert_eq!(editor.line_len(0), 7);
+
+        editor.set_pinned_cursor(0, 6);
+        editor.backward_delete();
+        assert_eq!(editor.lines, vec!["caf\u{E9} +"]);
+        editor.insert_char('\u{1F44D}');
+        assert_eq!(editor.cursor, (0, 6));
+
+        // a combining accent joins the letter before the cursor
+        editor.set_pinned_cursor(0, 1);
+        editor.insert_char('\u{301}');
+        assert_eq!(editor.lines, vec!["c\u{301}af\u{E9} \u{1F44D}+"]);
+        assert_eq!(editor.cursor, (0, 1));
+        editor.forward_delete();
+        assert_eq!(editor.lines, vec!["c\u{301}f\u{E9} \u{1F44D}+"]);
+
+        editor.move_cursor(CursorMove::Right, true);
+        editor.move_cursor(CursorMove::Right, true);
+        assert!(editor.in_selection(0, 2));
+        editor.cut_selection();
+        assert_eq!(editor.lines, vec!["c\u{301} \u{1F44D}+"]);
+        editor.newline();
+        editor.paste();
+        assert_eq!(editor.lines, vec!["c\u{301}", "f\u{E9} \u{1F44D}+"]);
+    }
+}
@@ -1,5 +1,11 @@
+use crate::breakpoint::{Condition, WatchKind, Watchpoint};
+use crate::config::{Config, Eof, Overflow, TapeMode};
+use crate::coverage::Coverage;
+use crate::history::{Checkpoint, History, Step};
 use crate::instruction::Instruction::{self, *};
 
+use std::collections::{BTreeMap, VecDeque};
+use std::ops::RangeInclusive;
 use tap::prelude::*;
 
 #[derive(Debug, Eq, PartialEq)]
@@ -21,43 +27,103 @@ impl Exception {
 
 pub type EngineResult = Result<(), Exception>;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum InstructionPointer {
     Start,
     End,
     Index(usize),
 }
 
+/// A loop that execution is inside of
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LoopFrame {
+    /// Index of the loop's `[`
+    pub open: usize,
+    /// Number of times the body has been entered since the loop was reached
+    pub iterations: usize,
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct Engine {
-    pub tape: Vec<u8>,
+    pub config: Config,
+    pub tape: VecDeque<u32>,
     pub tape_pointer: usize,
+    /// Position of cell 0 in `tape`, which moves right as an infinite tape grows to the left
+    pub tape_origin: usize,
     pub instructions: Vec<Instruction>,
+    pub jumps: Vec<Option<usize>>,
+    pub unmatched_jumps: Vec<usize>,
     pub instruction_pointer: InstructionPointer,
-    pub history: Vec<Instruction>,
+    /// Loops around the instruction pointer, outermost first
+    pub loop_stack: Vec<LoopFrame>,
+    /// Number of times each instruction has been executed to reach the current position
+    pub profile: Vec<u64>,
+    /// Times each `[` jumped past its loop, whose `]` then runs as if the body had
+    pub loop_skips: Vec<u64>,
+    /// What each instruction has done across runs, which undo and reset leave alone
+    pub coverage: Vec<Coverage>,
+    /// Instruction indices that execution stops in front of, when their condition holds
+    pub breakpoints: BTreeMap<usize, Option<Condition>>,
+    pub watchpoints: Vec<Watchpoint>,
+    /// Why the last step stopped on a conditional breakpoint or watchpoint
+    pub triggered: Option<String>,
+    pub history: History,
     pub output: Vec<u8>,
     pub input: Vec<u8>,
-    pub input_cell_history: Vec<u8>,
 }
 
 impl Engine {
     pub fn new(instructions: Vec<Instruction>) -> Engine {
+        let (jumps, unmatched_jumps) = Instruction::jump_table(&instructions);
+        let profile = vec![0; instructions.len()];
+        let loop_skips = vec![0; instructions.len()];
+        let coverage = vec![Coverage::default(); instructions.len()];
         Engine {
-            tape: vec![0],
+            config: Config::default(),
+            tape: VecDeque::from([0]),
             tape_pointer: 0,
+            tape_origin: 0,
             instructions,
+            jumps,
+            unmatched_jumps,
             instruction_pointer: InstructionPointer::Start,
-            history: vec![],
+            loop_stack: vec![],
+            profile,
+            loop_skips,
+            coverage,
+            breakpoints: BTreeMap::new(),
+            watchpoints: vec![],
+            triggered: None,
+            history: History::new(),
             output: vec![],
             input: vec![],
-            input_cell_history: vec![],
         }
     }
 
     pub fn load_instructions(&mut self, instructions: Vec<Instruction>) {
+        (self.jumps, self.unmatched_jumps) = Instruction::jump_table(&instructions);
+        self.profile.resize(instructions.len(), 0);
+        self.loop_skips.resize(instructions.len(), 0);
+        self.coverage
+            .resize(instructions.len(), Coverage::default());
         self.instructions = instructions;
     }
 
+    /// Whether a loop frame from before an edit still points at a `[`
+    fn is_loop_open(&self, open: usize) -> bool {
+        self.instructions.get(open) == Some(&JumpForward)
+    }
+
+    /// Keeps the instruction pointer on an instruction after the program shrinks
+    pub fn clamp_instruction_pointer(&mut self) {
+        if self.instructions.is_empty() {
+            self.instruction_pointer = InstructionPointer::Start;
+        } else if let InstructionPointer::Index(i) = self.instruction_pointer {
+            self.instruction_pointer =
+                InstructionPointer::Index(std::cmp::min(i, self.instructions.len() - 1));
+        }
+    }
+
     pub fn execute(&mut self, instruction: Instruction) -> EngineResult {
         match instruction {
             IncrementPointer => {
@@ -69,37 +135,41 @@ impl Engine {
                 self.next_instruction()
             }
             Increment => {
-                self.map_cell(|cell| cell.wrapping_add(1));
+                self.add_to_cell(true)?;
                 self.next_instruction()
             }
             Decrement => {
-                self.map_cell(|cell| cell.wrapping_sub(1));
+                self.add_to_cell(false)?;
                 self.next_instruction()
             }
             Output => {
-                self.output.push(self.cell());
+                self.output.push(self.cell() as u8);
                 self.next_instruction()
             }
-            Input => match self.pop_input() {
-                None => {
-                    let cell = self.cell();
-                    self.set_cell(0);
-                    self.input_cell_history.push(cell);
-                    self.next_instruction()
-                }
-                Some(input) => {
-                    let cell = self.cell();
-                    self.set_cell(input);
-                    self.input_cell_history.push(cell);
-                    self.next_instruction()
+            Input => {
+                let cell = self.cell();
+                let value = match (self.pop_input(), self.config.eof) {
+                    (Some(input), _) => input as u32,
+                    (None, Eof::Zero) => 0,
+                    (None, Eof::Max) => self.config.cell_width.max(),
+                    (None, Eof::Unchanged) => cell,
+                    (None, Eof::Ask) => return Exception::RequestingInput.result(),
+                };
+                self.set_cell(value);
+                self.next_instruction()
+            }
+            JumpForward => {
+                let open = self.instruction_pointer;
+                match self.cell() {
+                    0 => self.goto_matching_jump(),
+                    _ => self.next_instruction(),
                 }
-            },
-            JumpForward => match self.cell() {
-                0 => self.goto_matching_jump(),
-                _ => self.next_instruction(),
-            },
+                .tap_ok(|_| self.enter_loop(open))
+            }
             JumpBackward => match self.cell() {
-                0 => self.next_instruction(),
+                0 => self.next_instruction().tap_ok(|_| {
+                    self.loop_stack.pop();
+                }),
                 _ => self.goto_matching_jump(),
             },
             Breakpoint => {
@@ -109,8 +179,8 @@ impl Engine {
         }
     }
 
-    pub fn unexecute(&mut self, instruction: Instruction) -> EngineResult {
-        match instruction {
+    pub fn unexecute(&mut self, step: Step) -> EngineResult {
+        match step.instruction {
             IncrementPointer => {
                 self.prev_cell()?;
                 self.prev_instruction()
@@ -119,74 +189,391 @@ impl Engine {
                 self.next_cell()?;
                 self.prev_instruction()
             }
-            Increment => {
-                self.map_cell(|cell| cell.wrapping_sub(1));
-                self.prev_instruction()
-            }
-            Decrement => {
-                self.map_cell(|cell| cell.wrapping_add(1));
+            Increment | Decrement => {
+                self.set_cell(step.cell);
                 self.prev_instruction()
             }
             Output => {
                 self.output.pop();
                 self.prev_instruction()
             }
-            Input => match self.input_cell_history.pop() {
-                None => Exception::error("no input to undo").result(),
-                Some(cell) => {
-                    let input = self.cell();
-                    self.set_cell(cell);
+            Input => {
+                self.set_cell(step.cell);
+                if let Some(input) = step.input {
                     self.push_input(input);
-                    self.prev_instruction()
                 }
-            },
-            JumpForward => match self.cell() {
-                0 => self.goto_matching_jump(),
-                _ => self.prev_instruction(),
-            },
+                self.prev_instruction()
+            }
+            JumpForward => {
+                match self.loop_stack.last_mut() {
+                    Some(frame) if frame.iterations > 1 => frame.iterations -= 1,
+                    _ => {
+                        self.loop_stack.pop();
+                    }
+                }
+                match self.cell() {
+                    0 => self.goto_matching_jump(),
+                    _ => self.prev_instruction(),
+                }
+            }
             JumpBackward => match self.cell() {
-                0 => self.prev_instruction(),
+                0 => {
+                    let exited = step.exited.filter(|frame| self.is_loop_open(frame.open));
+                    self.loop_stack.extend(exited);
+                    self.prev_instruction()
+                }
                 _ => self.goto_matching_jump(),
             },
-            Breakpoint => {
-                self.prev_instruction()?;
-                Exception::Breakpoint.result()
-            }
+            Breakpoint => self.prev_instruction(),
+        }
+    }
+
+    /// Notes which way the instruction at `index` went, given the cell it ran on
+    fn record_coverage(&mut self, index: usize, instruction: Instruction, cell: u32) {
+        let coverage = &mut self.coverage[index];
+        let jumped = match instruction {
+            JumpForward => cell == 0,
+            JumpBackward => cell != 0,
+            _ => false,
+        };
+        if jumped {
+            coverage.jumped = true;
+        } else {
+            coverage.continued = true;
+        }
+    }
+
+    /// Counts an iteration of the loop at `open`, or starts a new frame when it's just been
+    /// reached. A loop that is skipped still gets a frame, which its `]` then leaves.
+    fn enter_loop(&mut self, open: InstructionPointer) {
+        let InstructionPointer::Index(open) = open else {
+            return;
+        };
+        match self.loop_stack.last_mut() {
+            Some(frame) if frame.open == open => frame.iterations += 1,
+            _ => self.loop_stack.push(LoopFrame {
+                open,
+                iterations: if self.cell() == 0 { 0 } else { 1 },
+            }),
         }
     }
 
     pub fn step(&mut self) -> EngineResult {
-        match self.current_instruction() {
-            Some(instruction) => self
-                .execute(instruction)
-                .tap(|_| self.history.push(instruction))
-                .tap_err(|e| {
-                    if e == &Exception::Breakpoint {
-                        self.history.push(instruction)
+        let InstructionPointer::Index(index) = self.instruction_pointer else {
+            return self.next_instruction();
+        };
+        let instruction = self.instructions[index];
+
+        if self.history.checkpoint_due() {
+            self.checkpoint(false);
+        }
+
+        self.triggered = None;
+        let (cell, next_input, input_len) =
+            (self.cell(), self.input.first().cloned(), self.input.len());
+        let exited = self
+            .loop_stack
+            .last()
+            .copied()
+            .filter(|_| instruction == JumpBackward && cell == 0);
+        self.execute(instruction)
+            .tap(|result| {
+                if matches!(result, Ok(()) | Err(Exception::Breakpoint)) {
+                    let input = next_input.filter(|_| self.input.len() < input_len);
+                    self.history.push(Step {
+                        instruction,
+                        cell,
+                        input,
+                        exited,
+                    });
+                    self.profile[index] += 1;
+                    if instruction == JumpForward && cell == 0 {
+                        self.loop_skips[index] += 1;
                     }
-                }),
-            None => self.next_instruction(),
+                    self.record_coverage(index, instruction, cell);
+                }
+            })
+            .and_then(|()| {
+                if self.watchpoint_triggered(instruction, cell) || self.at_breakpoint() {
+                    Exception::Breakpoint.result()
+                } else {
+                    Ok(())
+                }
+            })
+    }
+
+    /// Whether the next instruction has a breakpoint on it whose condition holds
+    pub fn at_breakpoint(&mut self) -> bool {
+        let InstructionPointer::Index(i) = self.instruction_pointer else {
+            return false;
+        };
+
+        match self.breakpoints.get(&i) {
+            Some(None) => true,
+            Some(Some(condition)) if condition.holds(self) => {
+                self.triggered = Some(format!("breakpoint condition: {condition}"));
+                true
+            }
+            _ => false,
         }
     }
 
+    /// Whether an instruction that just ran wrote to a watched cell
+    fn watchpoint_triggered(&mut self, instruction: Instruction, previous: u32) -> bool {
+        if !matches!(instruction, Increment | Decrement | Input) {
+            return false;
+        }
+
+        let (index, cell) = (self.cell_index(self.tape_pointer), self.cell());
+        let triggered = self.watchpoints.iter().any(|watchpoint| {
+            watchpoint.cell == index && (watchpoint.kind == WatchKind::Write || cell != previous)
+        });
+        if triggered {
+            self.triggered = Some(format!("watchpoint: cell {index} {previous} -> {cell}"));
+        }
+
+        triggered
+    }
+
+    /// Undoes the most recent step, replaying from a checkpoint when it has left the log
     pub fn undo(&mut self) -> EngineResult {
-        let instruction = self
+        if self.history.is_empty() {
+            return Exception::error("no previous instruction to undo").result();
+        }
+
+        match self.history.log.back().cloned() {
+            Some(step) => self.unexecute(step).tap_ok(|_| {
+                self.history.pop();
+                if let InstructionPointer::Index(i) = self.instruction_pointer {
+                    self.profile[i] = self.profile[i].saturating_sub(1);
+                    if step.instruction == JumpForward && step.cell == 0 {
+                        self.loop_skips[i] = self.loop_skips[i].saturating_sub(1);
+                    }
+                }
+            }),
+            None => self.rewind_to(self.history.len - 1),
+        }
+    }
+
+    /// Moves back to an earlier position in history
+    pub fn rewind_to(&mut self, position: usize) -> EngineResult {
+        if position > self.history.len {
+            return Exception::error(format!("no history at position {position}")).result();
+        }
+
+        if self.history.in_log(position) {
+            while self.history.len > position {
+                self.undo()?;
+            }
+            return Ok(());
+        }
+
+        let checkpoint = self
             .history
-            .last()
+            .checkpoint_before(position)
             .cloned()
-            .ok_or_else(|| Exception::error("no previous instruction to undo"))?;
+            .ok_or_else(|| Exception::error("no checkpoint to rewind from"))?;
+        self.history.truncate(position);
+        self.restore(&checkpoint);
+        self.replay_to(position)
+    }
 
-        self.unexecute(instruction)
-            .tap(|_| {
-                self.history.pop();
-            })
-            .tap_err(|e| {
-                if e == &Exception::Breakpoint {
-                    self.history.pop();
+    /// Moves back to the most recent position where running forward stopped on a breakpoint,
+    /// or to the start when there is none
+    pub fn rewind_to_breakpoint(&mut self) -> EngineResult {
+        self.rewind_until(|_, result| result == &Exception::Breakpoint.result())
+            .map(|_| ())
+    }
+
+    /// Moves back to the most recent time the loop opening at `open` was entered, stopping in
+    /// front of its `[`. Returns whether it was found.
+    pub fn rewind_to_loop_entry(&mut self, open: usize) -> Result<bool, Exception> {
+        self.rewind_until(|engine, _| {
+            // arriving at `[` by jumping back from its own `]` is another iteration, not an entry
+            let jumped_back = engine
+                .history
+                .log
+                .back()
+                .is_some_and(|step| step.instruction == JumpBackward && engine.cell() != 0);
+            engine.instruction_pointer == InstructionPointer::Index(open) && !jumped_back
+        })
+    }
+
+    /// The innermost loop containing the instruction at `index`, as the indices of its `[`
+    /// and `]`. A loop doesn't contain its own `[`.
+    pub fn enclosing_loop(&self, index: usize) -> Option<(usize, usize)> {
+        (0..index)
+            .rev()
+            .find_map(|open| match self.jumps.get(open) {
+                Some(&Some(close)) if self.instructions[open] == JumpForward && close >= index => {
+                    Some((open, close))
                 }
+                _ => None,
             })
     }
 
+    /// Moves back to the most recent position (before the current one) where `found` holds for
+    /// the state and the result of the step that reached it, or to the start when there is none.
+    /// Returns whether a position was found. Each checkpoint interval is replayed in turn to
+    /// search it, so this costs about as much as running forward the same distance.
+    pub fn rewind_until<F>(&mut self, mut found: F) -> Result<bool, Exception>
+    where
+        F: FnMut(&Engine, &EngineResult) -> bool,
+    {
+        // positions are searched for before `end`
+        let mut end = self.history.len;
+
+        while end > 1 {
+            let checkpoint = self
+                .history
+                .checkpoint_before(end - 2)
+                .cloned()
+                .ok_or_else(|| Exception::error("no checkpoint to rewind from"))?;
+            let stop = match self.history.edit_after(checkpoint.position) {
+                Some(edit) => std::cmp::min(edit - 1, end - 1),
+                None => end - 1,
+            };
+
+            self.history.truncate(stop);
+            self.restore(&checkpoint);
+
+            let mut latest = None;
+            while self.history.len < stop {
+                let result = self.step();
+                if let Err(Exception::Error(_) | Exception::RequestingInput) = result {
+                    return Self::diverged().map(|_| false);
+                }
+                if found(self, &result) {
+                    latest = Some(self.history.len);
+                }
+            }
+
+            if let Some(position) = latest {
+                return self.rewind_to(position).map(|_| true);
+            }
+            end = checkpoint.position + 1;
+        }
+
+        self.rewind_to(0).map(|_| false)
+    }
+
+    /// Applies an external change to the engine state as its own history entry, so it can be
+    /// undone like a step
+    pub fn edit<F: FnOnce(&mut Engine)>(&mut self, f: F) {
+        self.checkpoint(true);
+        f(self);
+        self.history.push_edit();
+        self.checkpoint(true);
+        if let Some(checkpoint) = self.history.checkpoints.last_mut() {
+            checkpoint.edit = true;
+        }
+    }
+
+    /// Sets a cell by index as an edit, allocating the tape up to it if it can grow
+    pub fn edit_cell(&mut self, index: isize, value: u32) -> EngineResult {
+        let max = self.config.cell_width.max();
+        if value > max {
+            return Exception::error(format!("{value} is more than the cell maximum {max}"))
+                .result();
+        }
+        let (left, right) = self.cells_to_allocate(index)?;
+        self.edit(|engine| {
+            let position = engine.allocate(left, right, index);
+            engine.tape[position] = value;
+        });
+        Ok(())
+    }
+
+    /// Moves the tape pointer to a cell by index as an edit, allocating the tape up to it if
+    /// it can grow
+    pub fn edit_tape_pointer(&mut self, index: isize) -> EngineResult {
+        let (left, right) = self.cells_to_allocate(index)?;
+        self.edit(|engine| engine.tape_pointer = engine.allocate(left, right, index));
+        Ok(())
+    }
+
+    /// Number of cells to add to the left and right of the tape to reach a cell
+    fn cells_to_allocate(&self, index: isize) -> Result<(usize, usize), Exception> {
+        if !self.cell_range().contains(&index) {
+            return Exception::error(format!("cell {index} is outside the tape")).result();
+        }
+        let position = index as i128 + self.tape_origin as i128;
+        let left = (-position).max(0) as usize;
+        let right = (position - self.tape.len() as i128 + 1).max(0) as usize;
+
+        match self.config.max_cells {
+            Some(max) if self.tape.len() + left + right > max => {
+                Exception::error(format!("tape exceeded the limit of {max} cells")).result()
+            }
+            _ => Ok((left, right)),
+        }
+    }
+
+    /// Grows the tape by the given number of cells either side, returning the position of a cell
+    fn allocate(&mut self, left: usize, right: usize, index: isize) -> usize {
+        for _ in 0..left {
+            self.tape.push_front(0);
+        }
+        self.tape.resize(self.tape.len() + right, 0);
+        self.tape_origin += left;
+        self.tape_pointer += left;
+        (index + self.tape_origin as isize) as usize
+    }
+
+    fn checkpoint(&mut self, pinned: bool) {
+        self.history.add_checkpoint(Checkpoint {
+            position: self.history.len,
+            tape: self.tape.clone(),
+            tape_pointer: self.tape_pointer,
+            tape_origin: self.tape_origin,
+            instruction_pointer: self.instruction_pointer,
+            loop_stack: self.loop_stack.clone(),
+            profile: self.profile.clone(),
+            loop_skips: self.loop_skips.clone(),
+            output_len: self.output.len(),
+            input: self.input.clone(),
+            pinned,
+            edit: false,
+        });
+    }
+
+    fn restore(&mut self, checkpoint: &Checkpoint) {
+        self.tape = checkpoint.tape.clone();
+        self.tape_pointer = checkpoint.tape_pointer;
+        self.tape_origin = checkpoint.tape_origin;
+        self.instruction_pointer = checkpoint.instruction_pointer;
+        self.profile = checkpoint.profile.clone();
+        // the program may have been edited since the checkpoint
+        self.clamp_instruction_pointer();
+        self.loop_stack = checkpoint
+            .loop_stack
+            .iter()
+            .filter(|frame| self.is_loop_open(frame.open))
+            .copied()
+            .collect();
+        self.profile.resize(self.instructions.len(), 0);
+        self.loop_skips = checkpoint.loop_skips.clone();
+        self.loop_skips.resize(self.instructions.len(), 0);
+        self.output.truncate(checkpoint.output_len);
+        self.input = checkpoint.input.clone();
+        self.history.restore(checkpoint);
+    }
+
+    /// Steps forward from a restored checkpoint, which should retrace the original run
+    fn replay_to(&mut self, position: usize) -> EngineResult {
+        while self.history.len < position {
+            match self.step() {
+                Ok(()) | Err(Exception::Breakpoint) => {}
+                Err(_) => return Self::diverged(),
+            }
+        }
+        Ok(())
+    }
+
+    fn diverged() -> EngineResult {
+        Exception::error("history no longer matches the program, reset to continue").result()
+    }
+
     pub fn goto(&mut self, instruction_index: usize) -> EngineResult {
         if instruction_index < self.instructions.len() {
             self.instruction_pointer = InstructionPointer::Index(instruction_index);
@@ -202,13 +589,21 @@ impl Engine {
     }
 
     pub fn reset(&mut self) {
-        self.tape = vec![0];
-        self.tape_pointer = 0;
+        let cells = if self.config.tape.is_fixed_size() {
+            self.config.tape_size
+        } else {
+            self.config.tape_start + 1
+        };
+        self.tape = VecDeque::from(vec![0; cells]);
+        self.tape_pointer = self.config.tape_start;
+        self.tape_origin = 0;
         self.instruction_pointer = InstructionPointer::Start;
-        self.history = vec![];
+        self.loop_stack.clear();
+        self.profile = vec![0; self.instructions.len()];
+        self.loop_skips = vec![0; self.instructions.len()];
+        self.history.clear();
         self.output = vec![];
         self.input = vec![];
-        self.input_cell_history = vec![];
     }
 
     pub fn current_instruction(&self) -> Option<Instruction> {
@@ -269,43 +664,143 @@ impl Engine {
             InstructionPointer::Index(i) => Ok(i),
             _ => err().result(),
         }?;
-        let i = Instruction::matching_jump(start, &self.instructions).ok_or_else(err)?;
+        let i = self.jumps.get(start).copied().flatten().ok_or_else(err)?;
         self.goto(i)
     }
 
     pub fn next_cell(&mut self) -> EngineResult {
-        self.tape_pointer += 1;
-        // expand the tape if the cell is new
-        if self.tape_pointer == self.tape.len() {
-            self.tape.push(0);
+        if self.tape_pointer + 1 < self.tape.len() {
+            self.tape_pointer += 1;
+            return Ok(());
         }
 
-        Ok(())
+        match self.config.tape {
+            TapeMode::Fixed => Exception::error(format!(
+                "can't move past the last cell ({})",
+                self.cell_index(self.tape_pointer)
+            ))
+            .result(),
+            TapeMode::Wrapping => {
+                self.tape_pointer = 0;
+                Ok(())
+            }
+            // expand the tape as the cell is new
+            TapeMode::Unbounded | TapeMode::Infinite => {
+                self.check_tape_capacity()?;
+                self.tape.push_back(0);
+                self.tape_pointer += 1;
+                Ok(())
+            }
+        }
     }
 
     pub fn prev_cell(&mut self) -> EngineResult {
-        if self.tape_pointer != 0 {
+        if self.tape_pointer > 0 {
             self.tape_pointer -= 1;
-            Ok(())
-        } else {
-            Exception::error("can't decrement intruction pointer: already at first instruction")
-                .result()
+            return Ok(());
+        }
+
+        match self.config.tape {
+            TapeMode::Unbounded | TapeMode::Fixed => Exception::error(format!(
+                "can't move before the first cell ({})",
+                self.cell_index(self.tape_pointer)
+            ))
+            .result(),
+            TapeMode::Wrapping => {
+                self.tape_pointer = self.tape.len() - 1;
+                Ok(())
+            }
+            // expand the tape to the left, shifting the origin along with the existing cells
+            TapeMode::Infinite => {
+                self.check_tape_capacity()?;
+                self.tape.push_front(0);
+                self.tape_origin += 1;
+                Ok(())
+            }
         }
     }
 
-    pub fn cell(&self) -> u8 {
+    fn check_tape_capacity(&self) -> EngineResult {
+        match self.config.max_cells {
+            Some(max) if self.tape.len() >= max => {
+                Exception::error(format!("tape exceeded the limit of {max} cells")).result()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Converts a position in `tape` to the index of the cell as seen by the program
+    pub fn cell_index(&self, position: usize) -> isize {
+        position as isize - self.tape_origin as isize
+    }
+
+    /// The value of a cell by its index as seen by the program, if it has been allocated
+    pub fn cell_at(&self, index: isize) -> Option<u32> {
+        let position = usize::try_from(index + self.tape_origin as isize).ok()?;
+        self.tape.get(position).cloned()
+    }
+
+    /// The range of cell indices the tape can ever hold, including those not yet allocated
+    pub fn cell_range(&self) -> RangeInclusive<isize> {
+        match self.config.tape {
+            TapeMode::Unbounded => 0..=isize::MAX,
+            TapeMode::Infinite => isize::MIN..=isize::MAX,
+            TapeMode::Fixed | TapeMode::Wrapping => 0..=self.config.tape_size as isize - 1,
+        }
+    }
+
+    /// The index of the rightmost cell that isn't zero
+    pub fn highest_nonzero_cell(&self) -> Option<isize> {
+        self.tape
+            .iter()
+            .rposition(|&cell| cell != 0)
+            .map(|position| self.cell_index(position))
+    }
+
+    pub fn cell(&self) -> u32 {
         self.tape[self.tape_pointer]
     }
 
-    pub fn set_cell(&mut self, value: u8) {
+    pub fn set_cell(&mut self, value: u32) {
         self.tape[self.tape_pointer] = value;
     }
 
-    pub fn map_cell(&mut self, f: fn(u8) -> u8) {
+    pub fn map_cell(&mut self, f: fn(u32) -> u32) {
         let value = self.cell();
         self.set_cell(f(value));
     }
 
+    /// Increments (or decrements) the current cell, applying the configured overflow policy
+    /// when the result doesn't fit in the cell width
+    pub fn add_to_cell(&mut self, increment: bool) -> EngineResult {
+        let max = self.config.cell_width.max();
+        let (cell, limit) = (self.cell(), if increment { max } else { 0 });
+
+        if cell != limit {
+            self.set_cell(if increment { cell + 1 } else { cell - 1 });
+            return Ok(());
+        }
+
+        match self.config.overflow {
+            Overflow::Wrap => {
+                self.set_cell(if increment { 0 } else { max });
+                Ok(())
+            }
+            Overflow::Saturate => Ok(()),
+            Overflow::Error => Exception::error(format!(
+                "cell {} {} ({}-bit cells)",
+                self.cell_index(self.tape_pointer),
+                if increment {
+                    "overflowed"
+                } else {
+                    "underflowed"
+                },
+                self.config.cell_width.bits()
+            ))
+            .result(),
+        }
+    }
+
     pub fn pop_input(&mut self) -> Option<u8> {
         let head = self.input.first().cloned();
         if head.is_some() {
@@ -329,6 +824,7 @@ impl Engine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::CellWidth;
 
     fn ok(result: EngineResult) {
         assert_eq!(result, Ok(()))
@@ -365,6 +861,494 @@ mod tests {
         assert_eq!(engine.instruction_pointer, InstructionPointer::Index(1));
     }
 
+    fn run(engine: &mut Engine, steps: usize) {
+        for _ in 0..steps {
+            ok(engine.step());
+        }
+    }
+
+    fn undo(engine: &mut Engine, steps: usize) {
+        for _ in 0..steps {
+            ok(engine.undo());
+        }
+    }
+
+    #[test]
+    fn cells_wrap_at_their_width() {
+        let mut engine = Engine::new(vec![Decrement, Decrement, Increment, Increment]);
+        engine.config.cell_width = CellWidth::U16;
+        ok(engine.next_instruction());
+        run(&mut engine, 1);
+        assert_eq!(engine.cell(), 65535);
+        run(&mut engine, 3);
+        assert_eq!(engine.cell(), 0);
+        undo(&mut engine, 4);
+        assert_eq!(engine.cell(), 0);
+    }
+
+    #[test]
+    fn saturated_cells_undo_exactly() {
+        let mut engine = Engine::new(vec![Decrement, Increment, Decrement, Decrement]);
+        engine.config.overflow = Overflow::Saturate;
+        ok(engine.next_instruction());
+        run(&mut engine, 4);
+        assert_eq!(engine.cell(), 0);
+        undo(&mut engine, 1);
+        assert_eq!(engine.cell(), 0);
+        undo(&mut engine, 1);
+        assert_eq!(engine.cell(), 1);
+        undo(&mut engine, 1);
+        assert_eq!(engine.cell(), 0);
+        undo(&mut engine, 1);
+        assert_eq!(engine.cell(), 0);
+        assert!(engine.history.is_empty());
+    }
+
+    #[test]
+    fn overflow_errors_leave_state_unchanged() {
+        let mut engine = Engine::new(vec![Increment, Decrement, Decrement]);
+        engine.config.overflow = Overflow::Error;
+        ok(engine.next_instruction());
+        run(&mut engine, 2);
+        assert!(engine.step().is_err());
+        assert_eq!(engine.cell(), 0);
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(2));
+        assert_eq!(engine.history.len, 2);
+    }
+
+    #[test]
+    fn eof_conventions() {
+        let cases = [(Eof::Zero, 0), (Eof::Max, 255), (Eof::Unchanged, 7)];
+        for (eof, expected) in cases {
+            let mut engine = Engine::new(vec![Input, Input]);
+            engine.config.eof = eof;
+            engine.input = vec![7];
+            ok(engine.next_instruction());
+            run(&mut engine, 2);
+            assert_eq!(engine.cell(), expected, "{eof}");
+
+            // undoing an input at the end of input must not invent new input
+            undo(&mut engine, 1);
+            assert_eq!(engine.cell(), 7);
+            assert!(engine.input.is_empty());
+            undo(&mut engine, 1);
+            assert_eq!(engine.cell(), 0);
+            assert_eq!(engine.input, vec![7]);
+        }
+    }
+
+    #[test]
+    fn eof_ask_requests_input_without_advancing() {
+        let mut engine = Engine::new(vec![Input, Output]);
+        engine.config.eof = Eof::Ask;
+        ok(engine.next_instruction());
+        assert_eq!(engine.step(), Err(Exception::RequestingInput));
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(0));
+        assert!(engine.history.is_empty());
+
+        engine.input = vec![42];
+        run(&mut engine, 2);
+        assert_eq!(engine.output, vec![42]);
+    }
+
+    fn tape_engine(tape: TapeMode, instructions: Vec<Instruction>) -> Engine {
+        let mut engine = Engine::new(instructions);
+        engine.config.tape = tape;
+        engine.config.tape_size = 3;
+        engine.config.tape_start = 1;
+        engine.reset();
+        ok(engine.next_instruction());
+        engine
+    }
+
+    #[test]
+    fn fixed_tape_errors_at_either_end() {
+        let mut engine = tape_engine(TapeMode::Fixed, vec![IncrementPointer, IncrementPointer]);
+        assert_eq!(engine.tape.len(), 3);
+        run(&mut engine, 1);
+        assert!(engine.step().is_err());
+        assert_eq!(engine.tape_pointer, 2);
+
+        let mut engine = tape_engine(TapeMode::Fixed, vec![DecrementPointer, DecrementPointer]);
+        run(&mut engine, 1);
+        assert!(engine.step().is_err());
+        assert_eq!(engine.tape_pointer, 0);
+    }
+
+    #[test]
+    fn wrapping_tape_wraps_and_unwraps() {
+        let mut engine = tape_engine(TapeMode::Wrapping, vec![DecrementPointer; 2]);
+        run(&mut engine, 2);
+        assert_eq!(engine.tape_pointer, 2);
+        undo(&mut engine, 1);
+        assert_eq!(engine.tape_pointer, 0);
+        undo(&mut engine, 1);
+        assert_eq!(engine.tape_pointer, 1);
+    }
+
+    #[test]
+    fn infinite_tape_grows_left() {
+        let mut engine = tape_engine(TapeMode::Infinite, vec![DecrementPointer; 3]);
+        run(&mut engine, 3);
+        assert_eq!(engine.tape.len(), 4);
+        assert_eq!(engine.tape_pointer, 0);
+        assert_eq!(engine.cell_index(engine.tape_pointer), -2);
+        undo(&mut engine, 3);
+        assert_eq!(engine.cell_index(engine.tape_pointer), 1);
+    }
+
+    #[test]
+    fn finds_the_highest_nonzero_cell() {
+        let mut engine = tape_engine(TapeMode::Infinite, vec![Increment]);
+        assert_eq!(engine.highest_nonzero_cell(), None);
+        engine.tape = VecDeque::from([1, 0, 2, 0]);
+        engine.tape_origin = 1;
+        assert_eq!(engine.highest_nonzero_cell(), Some(1));
+        assert_eq!(engine.cell_range(), isize::MIN..=isize::MAX);
+    }
+
+    #[test]
+    fn cell_edits_grow_the_tape_and_undo() {
+        let mut engine = tape_engine(TapeMode::Infinite, vec![Increment]);
+        ok(engine.edit_cell(-3, 7));
+        assert_eq!(engine.tape, [7, 0, 0, 0, 0]);
+        assert_eq!(engine.cell_index(engine.tape_pointer), 1);
+
+        ok(engine.edit_tape_pointer(2));
+        assert_eq!(engine.cell_index(engine.tape_pointer), 2);
+        assert_eq!(engine.tape.len(), 6);
+        run(&mut engine, 1);
+        assert_eq!(engine.cell_at(2), Some(1));
+
+        undo(&mut engine, 2);
+        assert_eq!(engine.tape, [7, 0, 0, 0, 0]);
+        assert_eq!(engine.cell_index(engine.tape_pointer), 1);
+        undo(&mut engine, 1);
+        assert_eq!(engine.tape, [0, 0]);
+        assert!(engine.history.is_empty());
+
+        assert!(engine.edit_cell(0, 256).is_err());
+        engine.config.tape = TapeMode::Fixed;
+        assert!(engine.edit_tape_pointer(3).is_err());
+        assert!(engine.history.is_empty());
+    }
+
+    #[test]
+    fn growing_tape_respects_the_cell_limit() {
+        let mut engine = tape_engine(TapeMode::Unbounded, vec![IncrementPointer; 3]);
+        engine.config.max_cells = Some(3);
+        run(&mut engine, 1);
+        assert!(engine.step().is_err());
+        assert_eq!(engine.tape.len(), 3);
+    }
+
+    fn small_history_engine(instructions: Vec<Instruction>) -> Engine {
+        let mut engine = Engine::new(instructions);
+        engine.history = History::with_capacity(4, 3, 4);
+        ok(engine.next_instruction());
+        engine
+    }
+
+    fn snapshot(engine: &Engine) -> (VecDeque<u32>, usize, InstructionPointer, Vec<u8>, Vec<u8>) {
+        let tape = engine.tape.clone();
+        let (input, output) = (engine.input.clone(), engine.output.clone());
+        (
+            tape,
+            engine.tape_pointer,
+            engine.instruction_pointer,
+            input,
+            output,
+        )
+    }
+
+    #[test]
+    fn undo_beyond_the_log_replays_from_checkpoints() {
+        // ,[.>+<-,]
+        let mut engine = small_history_engine(vec![
+            Input,
+            JumpForward,
+            Output,
+            IncrementPointer,
+            Increment,
+            DecrementPointer,
+            Decrement,
+            Input,
+            JumpBackward,
+        ]);
+        engine.input = vec![3, 2, 1];
+
+        let mut snapshots = vec![snapshot(&engine)];
+        while engine.instruction_pointer != InstructionPointer::End {
+            ok(engine.step());
+            snapshots.push(snapshot(&engine));
+        }
+        assert_eq!(engine.output, vec![3, 2, 1]);
+        assert!(engine.history.checkpoints.len() <= 4);
+
+        while let Some(expected) = snapshots.pop() {
+            assert_eq!(snapshot(&engine), expected, "at {}", engine.history.len);
+            if !snapshots.is_empty() {
+                ok(engine.undo());
+            }
+        }
+        assert!(engine.history.is_empty());
+    }
+
+    #[test]
+    fn rewind_to_breakpoint_stops_where_running_forward_did() {
+        let mut engine = small_history_engine(
+            [vec![Increment; 5], vec![Breakpoint], vec![Increment; 10]].concat(),
+        );
+        while engine.instruction_pointer != InstructionPointer::End {
+            engine.step().ok();
+        }
+        assert_eq!(engine.cell(), 15);
+
+        ok(engine.rewind_to_breakpoint());
+        assert_eq!(engine.history.len, 6);
+        assert_eq!(engine.cell(), 5);
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(6));
+
+        ok(engine.rewind_to_breakpoint());
+        assert!(engine.history.is_empty());
+        assert_eq!(engine.cell(), 0);
+    }
+
+    #[test]
+    fn rewind_until_finds_the_latest_match() {
+        let mut engine = small_history_engine(vec![Increment; 20]);
+        run(&mut engine, 20);
+
+        let found = engine.rewind_until(|engine, _| engine.cell() % 7 == 0);
+        assert_eq!(found, Ok(true));
+        assert_eq!(engine.cell(), 14);
+
+        let found = engine.rewind_until(|engine, _| engine.cell() > 14);
+        assert_eq!(found, Ok(false));
+        assert!(engine.history.is_empty());
+    }
+
+    #[test]
+    fn finds_enclosing_loops() {
+        let engine = Engine::new(vec![
+            JumpForward,
+            JumpForward,
+            Increment,
+            JumpBackward,
+            JumpBackward,
+        ]);
+        assert_eq!(engine.enclosing_loop(0), None);
+        assert_eq!(engine.enclosing_loop(1), Some((0, 4)));
+        assert_eq!(engine.enclosing_loop(2), Some((1, 3)));
+        assert_eq!(engine.enclosing_loop(3), Some((1, 3)));
+        assert_eq!(engine.enclosing_loop(4), Some((0, 4)));
+    }
+
+    #[test]
+    fn rewinds_to_the_latest_loop_entry() {
+        // ++[>+[-]<-] enters the inner loop once per iteration of the outer one
+        let mut engine = small_history_engine(vec![
+            Increment,
+            Increment,
+            JumpForward,
+            IncrementPointer,
+            Increment,
+            JumpForward,
+            Decrement,
+            JumpBackward,
+            DecrementPointer,
+            Decrement,
+            JumpBackward,
+        ]);
+        while engine.instruction_pointer != InstructionPointer::End {
+            ok(engine.step());
+        }
+        let end = engine.history.len;
+
+        assert_eq!(engine.rewind_to_loop_entry(5), Ok(true));
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(5));
+        assert_eq!(engine.tape[0], 1);
+        assert_eq!(engine.cell(), 1);
+        assert!(engine.history.len < end);
+
+        assert_eq!(engine.rewind_to_loop_entry(2), Ok(true));
+        assert_eq!(engine.history.len, 2);
+        assert_eq!(engine.rewind_to_loop_entry(2), Ok(false));
+        assert!(engine.history.is_empty());
+    }
+
+    #[test]
+    fn loop_stack_counts_iterations() {
+        // ++[>+[-]<-]
+        let mut engine = small_history_engine(vec![
+            Increment,
+            Increment,
+            JumpForward,
+            IncrementPointer,
+            Increment,
+            JumpForward,
+            Decrement,
+            JumpBackward,
+            DecrementPointer,
+            Decrement,
+            JumpBackward,
+        ]);
+        let frame = |open, iterations| LoopFrame { open, iterations };
+
+        let mut stacks = vec![engine.loop_stack.clone()];
+        while engine.instruction_pointer != InstructionPointer::End {
+            ok(engine.step());
+            stacks.push(engine.loop_stack.clone());
+        }
+        assert_eq!(stacks[3], vec![frame(2, 1)]);
+        assert_eq!(stacks[6], vec![frame(2, 1), frame(5, 1)]);
+        assert!(stacks.contains(&vec![frame(2, 2), frame(5, 1)]));
+        assert_eq!(stacks.last(), Some(&vec![]));
+
+        // undoing past the log replays from checkpoints, which must agree with the steps
+        while let Some(expected) = stacks.pop() {
+            assert_eq!(engine.loop_stack, expected);
+            if !stacks.is_empty() {
+                ok(engine.undo());
+            }
+        }
+    }
+
+    #[test]
+    fn profile_follows_undo() {
+        let mut engine = small_history_engine(vec![
+            Increment,
+            Increment,
+            JumpForward,
+            Decrement,
+            JumpBackward,
+        ]);
+        while engine.instruction_pointer != InstructionPointer::End {
+            ok(engine.step());
+        }
+        assert_eq!(engine.profile, vec![1, 1, 2, 2, 2]);
+
+        ok(engine.rewind_to(2));
+        assert_eq!(engine.profile, vec![1, 1, 0, 0, 0]);
+        run(&mut engine, 2);
+        undo(&mut engine, 1);
+        assert_eq!(engine.profile, vec![1, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn loop_skips_follow_undo() {
+        let mut engine = small_history_engine(vec![JumpForward, Increment, JumpBackward]);
+        while engine.instruction_pointer != InstructionPointer::End {
+            ok(engine.step());
+        }
+        assert_eq!(engine.loop_skips, vec![1, 0, 0]);
+
+        undo(&mut engine, 1);
+        assert_eq!(engine.loop_skips, vec![1, 0, 0]);
+        undo(&mut engine, 1);
+        assert_eq!(engine.loop_skips, vec![0, 0, 0]);
+        run(&mut engine, 1);
+        ok(engine.rewind_to(0));
+        assert_eq!(engine.loop_skips, vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn skipped_loops_leave_the_stack_alone() {
+        let mut engine = Engine::new(vec![
+            Increment,
+            JumpForward,
+            JumpForward,
+            JumpBackward,
+            JumpBackward,
+        ]);
+        ok(engine.next_instruction());
+        run(&mut engine, 2);
+        assert_eq!(
+            engine.loop_stack,
+            vec![LoopFrame {
+                open: 1,
+                iterations: 1
+            }]
+        );
+
+        engine.set_cell(0);
+        run(&mut engine, 2);
+        assert_eq!(
+            engine.loop_stack,
+            vec![LoopFrame {
+                open: 1,
+                iterations: 1
+            }]
+        );
+        undo(&mut engine, 2);
+        assert_eq!(
+            engine.loop_stack,
+            vec![LoopFrame {
+                open: 1,
+                iterations: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn edits_are_undone_as_their_own_entry() {
+        let mut engine = small_history_engine(vec![Input; 8]);
+        engine.config.eof = Eof::Ask;
+        engine.input = vec![1, 2, 3, 4, 5];
+        run(&mut engine, 5);
+        assert_eq!(engine.step(), Err(Exception::RequestingInput));
+
+        engine.edit(|engine| engine.input = vec![6, 7, 8]);
+        run(&mut engine, 3);
+        assert_eq!(engine.cell(), 8);
+
+        undo(&mut engine, 3);
+        assert_eq!(engine.input, vec![6, 7, 8]);
+        undo(&mut engine, 1);
+        assert!(engine.input.is_empty());
+        assert_eq!(engine.cell(), 5);
+        undo(&mut engine, 3);
+        assert_eq!(engine.input, vec![3, 4, 5]);
+        assert_eq!(engine.cell(), 2);
+    }
+
+    #[test]
+    fn conditional_breakpoints_only_stop_when_they_hold() {
+        let mut engine = Engine::new(vec![Increment, JumpForward, Increment, JumpBackward]);
+        engine
+            .breakpoints
+            .insert(2, Some("cell == 200".parse().unwrap()));
+        ok(engine.next_instruction());
+
+        while engine.step().is_ok() {}
+        assert_eq!(engine.cell(), 200);
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(2));
+        assert!(engine.triggered.is_some());
+    }
+
+    #[test]
+    fn watchpoints_stop_on_writes_and_changes() {
+        let mut engine = Engine::new(vec![Increment, IncrementPointer, Input, Increment]);
+        engine.watchpoints.push(Watchpoint {
+            cell: 1,
+            kind: WatchKind::Change,
+        });
+        engine.input = vec![0];
+        ok(engine.next_instruction());
+
+        // reading a 0 into a 0 cell doesn't change it
+        run(&mut engine, 3);
+        assert_eq!(engine.step(), Err(Exception::Breakpoint));
+        assert_eq!(
+            engine.triggered,
+            Some("watchpoint: cell 1 0 -> 1".to_string())
+        );
+
+        engine.watchpoints[0].kind = WatchKind::Write;
+        undo(&mut engine, 2);
+        assert_eq!(engine.step(), Err(Exception::Breakpoint));
+    }
+
     #[test]
     fn goto_matching_fails_gracefully_on_overrun() {
         let mut engine = engine();
@@ -0,0 +1,246 @@
+use crate::coverage;
+use crate::engine::{Exception, InstructionPointer};
+use crate::lint::{self, Format};
+use crate::profile;
+use crate::program::Program;
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+
+/// Exit status when the program finished normally
+pub const EXIT_SUCCESS: u8 = 0;
+/// Exit status when the program raised a runtime error
+pub const EXIT_RUNTIME_ERROR: u8 = 1;
+/// Exit status when the program could not be loaded or the arguments were invalid
+pub const EXIT_USAGE_ERROR: u8 = 2;
+/// Exit status when lint found something in the program
+pub const EXIT_LINT_FINDINGS: u8 = 3;
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Failure {
+    pub message: String,
+    /// Zero-based (line, column) of the instruction that failed
+    pub position: Option<(usize, usize)>,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "{}:{}: {}", line + 1, column + 1, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Runs a loaded program to completion on stdout, reporting errors on stderr
+pub fn run(program: &mut Program) -> ExitCode {
+    let (stdin, stdout) = (io::stdin(), io::stdout());
+    let result = execute(program, &mut stdin.lock(), &mut stdout.lock());
+
+    exit_code(program, result)
+}
+
+/// Runs a loaded program like `run`, then reports where its steps went on stderr, even when
+/// it failed part way
+pub fn profile(program: &mut Program) -> ExitCode {
+    let (stdin, stdout) = (io::stdin(), io::stdout());
+    let result = execute(program, &mut stdin.lock(), &mut stdout.lock());
+
+    eprint!("{}", profile::report(program));
+    exit_code(program, result)
+}
+
+/// Runs a loaded program once on each input file, or once on stdin when there are none,
+/// then writes lcov coverage of all the runs to stdout. Program output is discarded.
+pub fn coverage(program: &mut Program, inputs: &[String]) -> ExitCode {
+    let inputs = if inputs.is_empty() {
+        vec![None]
+    } else {
+        inputs.iter().map(Some).collect()
+    };
+
+    let mut status = EXIT_SUCCESS;
+    for input in inputs {
+        program.reset();
+        if let Some(path) = input {
+            match std::fs::read(path) {
+                Ok(bytes) => program.engine.input = bytes,
+                Err(e) => {
+                    eprintln!("plaque: {path}: {e}");
+                    return ExitCode::from(EXIT_USAGE_ERROR);
+                }
+            }
+        }
+
+        let result = execute(program, &mut io::stdin().lock(), &mut io::sink());
+        if exit_code(program, result) != ExitCode::from(EXIT_SUCCESS) {
+            status = EXIT_RUNTIME_ERROR;
+        }
+    }
+
+    print!("{}", coverage::lcov(program));
+    ExitCode::from(status)
+}
+
+/// Writes lint findings for a loaded program to stdout, without running it
+pub fn lint(program: &Program, format: Format) -> ExitCode {
+    match format {
+        Format::Text => print!("{}", lint::report(program)),
+        Format::Json => print!("{}", lint::json(program)),
+    }
+
+    if program.lints.is_empty() {
+        ExitCode::from(EXIT_SUCCESS)
+    } else {
+        ExitCode::from(EXIT_LINT_FINDINGS)
+    }
+}
+
+fn exit_code(program: &Program, result: Result<(), Failure>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::from(EXIT_SUCCESS),
+        Err(failure) => {
+            let filename = program
+                .editor
+                .filepath
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            eprintln!("{filename}:{failure}");
+            ExitCode::from(EXIT_RUNTIME_ERROR)
+        }
+    }
+}
+
+/// Steps the engine until the end of the instruction list, streaming any new output to `out`.
+/// Breakpoints are ignored, and requests for input read the next line of `input`.
+pub fn execute<R: BufRead, W: Write>(
+    program: &mut Program,
+    input: &mut R,
+    out: &mut W,
+) -> Result<(), Failure> {
+    if let Some(&i) = program.engine.unmatched_jumps.first() {
+        return Err(Failure {
+            message: format!("unmatched '{}'", program.engine.instructions[i].symbol()),
+            position: program.instruction_positions.get(i).copied(),
+        });
+    }
+
+    let mut written = 0;
+
+    while !program.engine.instructions.is_empty()
+        && program.engine.instruction_pointer != InstructionPointer::End
+    {
+        let result = program.engine.step();
+
+        let output = &program.engine.output[written..];
+        if !output.is_empty() {
+            out.write_all(output)
+                .and_then(|_| out.flush())
+                .map_err(|e| failure(program, format!("failed to write output: {e}")))?;
+            written = program.engine.output.len();
+        }
+
+        match result {
+            Ok(()) | Err(Exception::Breakpoint) => {}
+            Err(Exception::RequestingInput) => {
+                let mut line = vec![];
+                match input.read_until(b'\n', &mut line) {
+                    Ok(0) => {
+                        return Err(failure(
+                            program,
+                            "input requested after end of input".into(),
+                        ))
+                    }
+                    Ok(_) => program.engine.input.append(&mut line),
+                    Err(e) => {
+                        return Err(failure(program, format!("failed to read input: {e}")));
+                    }
+                }
+            }
+            Err(Exception::Error(message)) => {
+                return Err(failure(program, message));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn failure(program: &Program, message: String) -> Failure {
+    Failure {
+        message,
+        position: program.cursor(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Eof;
+
+    fn run_source(source: &str, input: &[u8]) -> (Result<(), Failure>, Vec<u8>) {
+        let mut program = Program::new();
+        program.load_source(source);
+        program.engine.input = input.to_vec();
+
+        let mut out = vec![];
+        let result = execute(&mut program, &mut &[][..], &mut out);
+        (result, out)
+    }
+
+    #[test]
+    fn streams_output_to_completion() {
+        let (result, out) = run_source("++++++++[>++++++++<-]>+.+.\n$+.", &[]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(out, b"ABC");
+    }
+
+    #[test]
+    fn echoes_input() {
+        let (result, out) = run_source(",[.,]", b"plaque");
+        assert_eq!(result, Ok(()));
+        assert_eq!(out, b"plaque");
+    }
+
+    #[test]
+    fn asks_for_input_line_by_line() {
+        let mut program = Program::new();
+        program.load_source(",[.,]");
+        program.engine.config.eof = Eof::Ask;
+
+        let mut out = vec![];
+        let result = execute(&mut program, &mut &b"ab\ncd"[..], &mut out);
+        assert_eq!(out, b"ab\ncd");
+        assert_eq!(
+            result.unwrap_err().message,
+            "input requested after end of input"
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_source_position() {
+        let (result, out) = run_source("+.\n  comment <", &[]);
+        let failure = result.unwrap_err();
+        assert_eq!(out, [1]);
+        assert_eq!(failure.position, Some((1, 10)));
+        assert!(failure.to_string().starts_with("2:11: "));
+    }
+
+    #[test]
+    fn refuses_to_run_unmatched_jumps() {
+        let (result, out) = run_source("+.\n[[-]", &[]);
+        let failure = result.unwrap_err();
+        assert!(out.is_empty());
+        assert_eq!(failure.position, Some((1, 0)));
+        assert_eq!(failure.message, "unmatched '['");
+    }
+
+    #[test]
+    fn empty_program_succeeds() {
+        let (result, out) = run_source("no instructions here", &[]);
+        assert_eq!(result, Ok(()));
+        assert!(out.is_empty());
+    }
+}
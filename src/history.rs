@@ -0,0 +1,252 @@
+use crate::engine::{InstructionPointer, LoopFrame};
+use crate::instruction::Instruction;
+
+use std::collections::VecDeque;
+
+const CHECKPOINT_INTERVAL: usize = 1024;
+const LOG_CAPACITY: usize = 1 << 16;
+const CHECKPOINT_CAPACITY: usize = 64;
+
+/// An executed instruction along with what it overwrote, so it can be undone exactly
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Step {
+    pub instruction: Instruction,
+    /// The current cell before the instruction ran
+    pub cell: u32,
+    /// The input consumed by the instruction, if any
+    pub input: Option<u8>,
+    /// The loop a `]` left, so it can be re-entered when undone
+    pub exited: Option<LoopFrame>,
+}
+
+/// A snapshot of the engine state at a position in history
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Checkpoint {
+    pub position: usize,
+    pub tape: VecDeque<u32>,
+    pub tape_pointer: usize,
+    pub tape_origin: usize,
+    pub instruction_pointer: InstructionPointer,
+    pub loop_stack: Vec<LoopFrame>,
+    pub profile: Vec<u64>,
+    pub loop_skips: Vec<u64>,
+    pub output_len: usize,
+    pub input: Vec<u8>,
+    /// Pinned checkpoints surround edits and are never thinned out
+    pub pinned: bool,
+    /// The position was reached by an edit rather than a step, so can't be replayed into
+    pub edit: bool,
+}
+
+/// Execution history made of a bounded log of recent steps, which can be undone directly,
+/// and periodic checkpoints, which older positions are restored from and replayed forward.
+/// When there are too many checkpoints, every other one is dropped and the interval doubles.
+#[derive(Debug, Eq, PartialEq)]
+pub struct History {
+    /// Number of entries (steps and edits) since the last reset
+    pub len: usize,
+    /// The most recent steps, oldest first
+    pub log: VecDeque<Step>,
+    /// Checkpoints ordered by position
+    pub checkpoints: Vec<Checkpoint>,
+    pub interval: usize,
+    base_interval: usize,
+    log_capacity: usize,
+    checkpoint_capacity: usize,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::with_capacity(CHECKPOINT_INTERVAL, LOG_CAPACITY, CHECKPOINT_CAPACITY)
+    }
+
+    pub fn with_capacity(
+        interval: usize,
+        log_capacity: usize,
+        checkpoint_capacity: usize,
+    ) -> History {
+        History {
+            len: 0,
+            log: VecDeque::new(),
+            checkpoints: vec![],
+            interval,
+            base_interval: interval,
+            log_capacity,
+            checkpoint_capacity,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = History::with_capacity(
+            self.base_interval,
+            self.log_capacity,
+            self.checkpoint_capacity,
+        );
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, step: Step) {
+        if self.log.len() == self.log_capacity {
+            self.log.pop_front();
+        }
+        self.log.push_back(step);
+        self.len += 1;
+    }
+
+    /// Removes the most recent step from the log, dropping any checkpoints after it
+    pub fn pop(&mut self) -> Option<Step> {
+        let step = self.log.pop_back()?;
+        self.len -= 1;
+        self.truncate(self.len);
+        Some(step)
+    }
+
+    /// Counts an edit as an entry. Steps before an edit can no longer be undone from the log.
+    pub fn push_edit(&mut self) {
+        self.log.clear();
+        self.len += 1;
+    }
+
+    /// Moves back to a checkpoint, forgetting the log
+    pub fn restore(&mut self, checkpoint: &Checkpoint) {
+        self.log.clear();
+        self.len = checkpoint.position;
+    }
+
+    /// Drops checkpoints after the given position
+    pub fn truncate(&mut self, position: usize) {
+        while self
+            .checkpoints
+            .last()
+            .is_some_and(|checkpoint| checkpoint.position > position)
+        {
+            self.checkpoints.pop();
+        }
+    }
+
+    pub fn checkpoint_due(&self) -> bool {
+        let latest = self
+            .checkpoints
+            .last()
+            .map(|checkpoint| checkpoint.position);
+        latest.is_none() || (self.len.is_multiple_of(self.interval) && latest < Some(self.len))
+    }
+
+    pub fn add_checkpoint(&mut self, mut checkpoint: Checkpoint) {
+        if let Some(latest) = self.checkpoints.last() {
+            if latest.position == checkpoint.position {
+                checkpoint.pinned |= latest.pinned;
+                checkpoint.edit |= latest.edit;
+                self.checkpoints.pop();
+            }
+        }
+        self.checkpoints.push(checkpoint);
+
+        while self.checkpoints.iter().filter(|c| !c.pinned).count() > self.checkpoint_capacity {
+            self.interval *= 2;
+            let interval = self.interval;
+            self.checkpoints.retain(|checkpoint| {
+                checkpoint.pinned || checkpoint.position.is_multiple_of(interval)
+            });
+        }
+    }
+
+    /// The latest checkpoint at or before the given position
+    pub fn checkpoint_before(&self, position: usize) -> Option<&Checkpoint> {
+        self.checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.position <= position)
+    }
+
+    /// The position of the first edit after the given position
+    pub fn edit_after(&self, position: usize) -> Option<usize> {
+        self.checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.edit && checkpoint.position > position)
+            .map(|checkpoint| checkpoint.position)
+    }
+
+    /// Whether the given position can be reached by undoing steps in the log
+    pub fn in_log(&self, position: usize) -> bool {
+        position <= self.len && self.len - position <= self.log.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint(position: usize) -> Checkpoint {
+        Checkpoint {
+            position,
+            tape: VecDeque::from([0]),
+            tape_pointer: 0,
+            tape_origin: 0,
+            instruction_pointer: InstructionPointer::Start,
+            loop_stack: vec![],
+            profile: vec![],
+            loop_skips: vec![],
+            output_len: 0,
+            input: vec![],
+            pinned: false,
+            edit: false,
+        }
+    }
+
+    fn step() -> Step {
+        Step {
+            instruction: Instruction::Increment,
+            cell: 0,
+            input: None,
+            exited: None,
+        }
+    }
+
+    #[test]
+    fn log_is_bounded() {
+        let mut history = History::with_capacity(4, 3, 4);
+        for _ in 0..5 {
+            history.push(step());
+        }
+        assert_eq!(history.len, 5);
+        assert_eq!(history.log.len(), 3);
+        assert!(history.in_log(2));
+        assert!(!history.in_log(1));
+    }
+
+    #[test]
+    fn checkpoints_thin_out_when_full() {
+        let mut history = History::with_capacity(2, 8, 4);
+        for position in (0..=12).step_by(2) {
+            history.len = position;
+            if history.checkpoint_due() {
+                history.add_checkpoint(checkpoint(position));
+            }
+        }
+
+        let positions: Vec<_> = history.checkpoints.iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![0, 4, 8, 12]);
+        assert_eq!(history.interval, 4);
+    }
+
+    #[test]
+    fn pinned_checkpoints_survive_thinning() {
+        let mut history = History::with_capacity(2, 8, 2);
+        history.add_checkpoint(checkpoint(0));
+        history.add_checkpoint(Checkpoint {
+            pinned: true,
+            ..checkpoint(3)
+        });
+        history.add_checkpoint(checkpoint(4));
+        history.add_checkpoint(checkpoint(6));
+
+        let positions: Vec<_> = history.checkpoints.iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![0, 3, 4]);
+        assert_eq!(history.checkpoint_before(5).unwrap().position, 4);
+        assert_eq!(history.checkpoint_before(3).unwrap().position, 3);
+    }
+}
@@ -47,6 +47,33 @@ impl Instruction {
         })
     }
 
+    /// Pairs up every '[' and ']' in a single pass, returning a table indexed by instruction
+    /// that holds the index of the matching jump, along with the indices of any unmatched jumps
+    pub fn jump_table(instructions: &[Instruction]) -> (Vec<Option<usize>>, Vec<usize>) {
+        let mut table = vec![None; instructions.len()];
+        let mut unmatched = vec![];
+        let mut open = vec![];
+
+        for (i, instruction) in instructions.iter().enumerate() {
+            match instruction {
+                JumpForward => open.push(i),
+                JumpBackward => match open.pop() {
+                    Some(j) => {
+                        table[i] = Some(j);
+                        table[j] = Some(i);
+                    }
+                    None => unmatched.push(i),
+                },
+                _ => {}
+            }
+        }
+
+        unmatched.append(&mut open);
+        unmatched.sort_unstable();
+
+        (table, unmatched)
+    }
+
     pub fn symbol(&self) -> char {
         match self {
             IncrementPointer => '>',
@@ -89,4 +116,27 @@ mod tests {
         assert_eq!(instr[2], Increment);
         assert_eq!(Instruction::matching_jump(2, &instr), None);
     }
+
+    #[test]
+    fn jump_table_agrees_with_matching() {
+        for line in ["[]++][[]][[++[+]+++]][+++][++[]", "[[+]", "[+]]", "][", ""] {
+            let instr = parse(line);
+            let (table, _) = Instruction::jump_table(&instr);
+            for (i, jump) in table.into_iter().enumerate() {
+                assert_eq!(jump, Instruction::matching_jump(i, &instr), "{line} at {i}");
+            }
+        }
+    }
+
+    #[test]
+    fn jump_table_reports_unmatched() {
+        let (_, unmatched) = Instruction::jump_table(&parse("[]++][[]][[++[+]+++]][+++][++[]"));
+        assert_eq!(unmatched, vec![4, 26]);
+
+        let (_, unmatched) = Instruction::jump_table(&parse("[[+]"));
+        assert_eq!(unmatched, vec![0]);
+
+        let (_, unmatched) = Instruction::jump_table(&parse("[-]"));
+        assert!(unmatched.is_empty());
+    }
 }
@@ -0,0 +1,489 @@
+use crate::config::{Overflow, TapeMode};
+use crate::engine::Engine;
+use crate::instruction::Instruction::{self, *};
+use crate::program::Program;
+
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// The program can't run, or is certain to fail when it does
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Lint {
+    UnmatchedBracket,
+    /// `+-`, `-+`, `<>` or `><`, which undo each other
+    CancellingSequence,
+    /// A loop straight after another loop, which always leaves the cell at zero
+    DeadLoop,
+    /// A loop that can't end once entered, as nothing in it changes the cell it tests
+    UnchangedLoopCell,
+    /// A `<` that is certain to move the pointer off the left end of the tape
+    PointerUnderflow,
+}
+
+impl Lint {
+    /// Stable name for the lint in machine-readable output
+    pub fn code(self) -> &'static str {
+        match self {
+            Lint::UnmatchedBracket => "unmatched-bracket",
+            Lint::CancellingSequence => "cancelling-sequence",
+            Lint::DeadLoop => "dead-loop",
+            Lint::UnchangedLoopCell => "unchanged-loop-cell",
+            Lint::PointerUnderflow => "pointer-underflow",
+        }
+    }
+
+    pub fn severity(self) -> Severity {
+        match self {
+            Lint::UnmatchedBracket | Lint::PointerUnderflow => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Finding {
+    pub lint: Lint,
+    /// First and last instruction the finding covers, which for a loop are its brackets
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+}
+
+/// How `plaque lint` writes its findings
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Format {
+    /// One finding per line, as `file:line:column: severity: message [code]`
+    #[default]
+    Text,
+    /// A JSON array with an object per finding
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("invalid format '{s}' (expected text or json)")),
+        }
+    }
+}
+
+/// Everything the analysis finds in the loaded instructions, in source order
+pub fn check(engine: &Engine) -> Vec<Finding> {
+    let mut findings = vec![];
+    unmatched_brackets(engine, &mut findings);
+    cancelling_sequences(engine, &mut findings);
+    dead_loops(engine, &mut findings);
+    unchanged_loop_cells(engine, &mut findings);
+    pointer_underflow(engine, &mut findings);
+
+    findings.sort_by_key(|finding| (finding.start, finding.end));
+    findings
+}
+
+fn unmatched_brackets(engine: &Engine, findings: &mut Vec<Finding>) {
+    for &i in &engine.unmatched_jumps {
+        findings.push(Finding {
+            lint: Lint::UnmatchedBracket,
+            start: i,
+            end: i,
+            message: format!("unmatched '{}'", engine.instructions[i].symbol()),
+        });
+    }
+}
+
+/// Whether a pointer move can't fail from the cell it starts at, if that's known, so that
+/// moving back again undoes it
+fn move_always_succeeds(
+    engine: &Engine,
+    instruction: Instruction,
+    position: Option<usize>,
+) -> bool {
+    let config = &engine.config;
+    match (instruction, config.tape) {
+        (_, TapeMode::Wrapping) => true,
+        // growing the tape fails at the cell limit
+        (IncrementPointer, TapeMode::Unbounded) | (_, TapeMode::Infinite) => {
+            config.max_cells.is_none()
+        }
+        (IncrementPointer, _) => position.is_some_and(|p| p + 1 < config.tape_size),
+        (_, _) => position.is_some_and(|p| p > 0),
+    }
+}
+
+fn cancelling_sequences(engine: &Engine, findings: &mut Vec<Finding>) {
+    let instructions = &engine.instructions;
+    let positions = pointer_positions(engine);
+    let mut i = 0;
+    while i + 1 < instructions.len() {
+        let (a, b) = (instructions[i], instructions[i + 1]);
+        // other overflow modes stop at the cell's bounds, where `+-` isn't a no-op
+        let cancels = match (a, b) {
+            (Increment, Decrement) | (Decrement, Increment) => {
+                engine.config.overflow == Overflow::Wrap
+            }
+            (IncrementPointer, DecrementPointer) | (DecrementPointer, IncrementPointer) => {
+                move_always_succeeds(engine, a, positions[i])
+            }
+            _ => false,
+        };
+        if !cancels {
+            i += 1;
+            continue;
+        }
+
+        findings.push(Finding {
+            lint: Lint::CancellingSequence,
+            start: i,
+            end: i + 1,
+            message: format!("'{}{}' cancels itself out", a.symbol(), b.symbol()),
+        });
+        i += 2;
+    }
+}
+
+/// The loops of matched brackets, as the indices of their `[` and `]`
+fn loops(engine: &Engine) -> impl Iterator<Item = (usize, usize)> + '_ {
+    engine
+        .jumps
+        .iter()
+        .enumerate()
+        .filter(|&(open, _)| engine.instructions[open] == JumpForward)
+        .filter_map(|(open, &close)| Some((open, close?)))
+}
+
+fn dead_loops(engine: &Engine, findings: &mut Vec<Finding>) {
+    for (open, close) in loops(engine) {
+        // breakpoints don't touch the cell, so they can sit between the loops
+        let previous = engine.instructions[..open]
+            .iter()
+            .rposition(|&instruction| instruction != Breakpoint);
+        let after_loop = previous
+            .is_some_and(|i| engine.instructions[i] == JumpBackward && engine.jumps[i].is_some());
+        if after_loop {
+            findings.push(Finding {
+                lint: Lint::DeadLoop,
+                start: open,
+                end: close,
+                message: "loop never runs, as the loop before it leaves the cell at zero"
+                    .to_string(),
+            });
+        }
+    }
+}
+
+fn unchanged_loop_cells(engine: &Engine, findings: &mut Vec<Finding>) {
+    for (open, close) in loops(engine) {
+        // only simple loops are provable, with nested loops left alone
+        let mut offset = 0;
+        let mut changed = false;
+        for instruction in &engine.instructions[open + 1..close] {
+            match instruction {
+                IncrementPointer => offset += 1,
+                DecrementPointer => offset -= 1,
+                Increment | Decrement | Input => changed |= offset == 0,
+                JumpForward | JumpBackward => changed = true,
+                Output | Breakpoint => {}
+            }
+        }
+
+        if !changed && offset == 0 {
+            findings.push(Finding {
+                lint: Lint::UnchangedLoopCell,
+                start: open,
+                end: close,
+                message: "loop never changes the cell it tests, so it can't end once entered"
+                    .to_string(),
+            });
+        }
+    }
+}
+
+/// How far a loop's body moves the pointer, if it's the same on every iteration
+fn loop_shift(engine: &Engine, open: usize, close: usize) -> Option<isize> {
+    let mut shift = 0;
+    let mut i = open + 1;
+    while i < close {
+        match engine.instructions[i] {
+            IncrementPointer => shift += 1,
+            DecrementPointer => shift -= 1,
+            JumpForward => {
+                let inner = engine.jumps[i]?;
+                if loop_shift(engine, i, inner)? != 0 {
+                    return None;
+                }
+                i = inner;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    Some(shift)
+}
+
+/// The cell the pointer is on before each instruction, where that's certain. A loop that
+/// leaves the pointer where it found it starts every iteration on the same cell, so
+/// positions carry on through it, up until a loop that moves the pointer or a move off the
+/// left end of the tape.
+fn pointer_positions(engine: &Engine) -> Vec<Option<usize>> {
+    let mut positions = vec![None; engine.instructions.len()];
+    let mut position = engine.config.tape_start;
+    for (i, &instruction) in engine.instructions.iter().enumerate() {
+        positions[i] = Some(position);
+        match instruction {
+            IncrementPointer => position += 1,
+            DecrementPointer => match position.checked_sub(1) {
+                Some(left) => position = left,
+                None => break,
+            },
+            JumpForward => match engine.jumps[i] {
+                Some(close) if loop_shift(engine, i, close) == Some(0) => {}
+                _ => break,
+            },
+            // the `[` of an unmatched `]` has already stopped the walk
+            JumpBackward if engine.jumps[i].is_none() => break,
+            _ => {}
+        }
+    }
+    positions
+}
+
+/// A `<` from cell 0, outside any loop so that it's certain to run
+fn pointer_underflow(engine: &Engine, findings: &mut Vec<Finding>) {
+    // only these tapes stop at the left end of cell 0
+    if !matches!(engine.config.tape, TapeMode::Unbounded | TapeMode::Fixed) {
+        return;
+    }
+
+    let positions = pointer_positions(engine);
+    let mut depth = 0;
+    for (i, &instruction) in engine.instructions.iter().enumerate() {
+        match instruction {
+            JumpForward => depth += 1,
+            JumpBackward => depth -= 1,
+            DecrementPointer if depth == 0 && positions[i] == Some(0) => {
+                findings.push(Finding {
+                    lint: Lint::PointerUnderflow,
+                    start: i,
+                    end: i,
+                    message: "'<' moves the pointer left of cell 0".to_string(),
+                });
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// One line per finding, with one-based positions of its first instruction
+pub fn report(program: &Program) -> String {
+    let source = source_name(program);
+    let mut report = String::new();
+    for finding in &program.lints {
+        let (line, column) = program.instruction_positions[finding.start];
+        writeln!(
+            report,
+            "{source}:{}:{}: {}: {} [{}]",
+            line + 1,
+            column + 1,
+            finding.lint.severity(),
+            finding.message,
+            finding.lint.code()
+        )
+        .unwrap();
+    }
+    report
+}
+
+/// A JSON array of findings, each with one-based positions of its first and last
+/// instructions
+pub fn json(program: &Program) -> String {
+    let source = json_string(&source_name(program));
+    let findings = program
+        .lints
+        .iter()
+        .map(|finding| {
+            let (line, column) = program.instruction_positions[finding.start];
+            let (end_line, end_column) = program.instruction_positions[finding.end];
+            format!(
+                "  {{\"file\": {source}, \"line\": {}, \"column\": {}, \"end_line\": {}, \
+                 \"end_column\": {}, \"severity\": \"{}\", \"code\": \"{}\", \"message\": {}}}",
+                line + 1,
+                column + 1,
+                end_line + 1,
+                end_column + 1,
+                finding.lint.severity(),
+                finding.lint.code(),
+                json_string(&finding.message)
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if findings.is_empty() {
+        "[]\n".to_string()
+    } else {
+        format!("[\n{}\n]\n", findings.join(",\n"))
+    }
+}
+
+fn source_name(program: &Program) -> String {
+    program
+        .editor
+        .filepath
+        .as_ref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_default()
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn lints(source: &str) -> Vec<(Lint, usize, usize)> {
+        let mut program = Program::new();
+        program.load_source(source);
+        program
+            .lints
+            .iter()
+            .map(|finding| (finding.lint, finding.start, finding.end))
+            .collect()
+    }
+
+    #[test]
+    fn finds_cancelling_sequences() {
+        assert_eq!(
+            lints("+-+ comment - ><>"),
+            vec![
+                (Lint::CancellingSequence, 0, 1),
+                (Lint::CancellingSequence, 2, 3),
+                (Lint::CancellingSequence, 4, 5),
+            ]
+        );
+        // a breakpoint between them is there on purpose
+        assert_eq!(lints("+$-"), vec![]);
+        // `<>` from cell 0 stops at the end of the tape, as does `><` from the last cell
+        assert_eq!(lints("<>"), vec![(Lint::PointerUnderflow, 0, 0)]);
+        assert_eq!(lints("+[<>-]"), vec![]);
+    }
+
+    #[test]
+    fn cancelling_follows_the_config() {
+        let lints_with = |source: &str, config: Config| {
+            let mut program = Program::new();
+            program.load_source(source);
+            program.configure(config);
+            program
+                .lints
+                .iter()
+                .map(|finding| (finding.lint, finding.start))
+                .collect::<Vec<_>>()
+        };
+
+        let saturate = Config {
+            overflow: Overflow::Saturate,
+            ..Config::default()
+        };
+        assert_eq!(lints_with("+-", saturate), vec![]);
+
+        let fixed = Config {
+            tape: TapeMode::Fixed,
+            tape_size: 2,
+            ..Config::default()
+        };
+        assert_eq!(
+            lints_with("><> ><", fixed),
+            vec![(Lint::CancellingSequence, 0)]
+        );
+
+        let infinite = Config {
+            tape: TapeMode::Infinite,
+            ..Config::default()
+        };
+        assert_eq!(
+            lints_with("<> +[<>-]", infinite),
+            vec![(Lint::CancellingSequence, 0), (Lint::CancellingSequence, 4)]
+        );
+    }
+
+    #[test]
+    fn finds_dead_and_stuck_loops() {
+        assert_eq!(
+            lints("+[-]$[.>+<] ,[>+<] [[-]>]"),
+            vec![
+                (Lint::DeadLoop, 5, 10),
+                (Lint::UnchangedLoopCell, 5, 10),
+                (Lint::UnchangedLoopCell, 12, 16),
+                (Lint::DeadLoop, 17, 22),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_pointer_underflow() {
+        assert_eq!(lints(">[-<+>]<<"), vec![(Lint::PointerUnderflow, 8, 8)]);
+        // after a loop that moves the pointer, its position isn't known
+        assert_eq!(lints("+[>]<<"), vec![]);
+
+        let mut program = Program::new();
+        program.load_source(">[-<+>]<<");
+        program.configure(Config {
+            tape: TapeMode::Infinite,
+            ..Config::default()
+        });
+        assert!(program.lints.is_empty());
+    }
+
+    #[test]
+    fn reports_text_and_json() {
+        let mut program = Program::new();
+        program.load_source("+\n[-\n");
+        assert_eq!(
+            report(&program),
+            ":2:1: error: unmatched '[' [unmatched-bracket]\n"
+        );
+        assert_eq!(
+            json(&program),
+            "[\n  {\"file\": \"\", \"line\": 2, \"column\": 1, \"end_line\": 2, \
+             \"end_column\": 1, \"severity\": \"error\", \"code\": \"unmatched-bracket\", \
+             \"message\": \"unmatched '['\"}\n]\n"
+        );
+
+        program.load_source("+");
+        assert_eq!(json(&program), "[]\n");
+    }
+}
@@ -2,25 +2,90 @@
 #![feature(iter_intersperse)]
 
 mod app;
+mod args;
+mod breakpoint;
+mod config;
+mod coverage;
 mod editor;
 mod engine;
+mod headless;
+mod history;
 mod instruction;
+mod lint;
+mod profile;
 mod program;
+mod search;
 mod ui;
 
+use args::{Args, Command};
 use program::Program;
 
 use anyhow::Result;
+use std::process::ExitCode;
 
-fn main() -> Result<()> {
-    let input_filepath = std::env::args().nth(1);
-
-    let mut program = match input_filepath {
-        Some(filepath) => Program::load(filepath)?,
-        None => Program::blank(),
+fn main() -> Result<ExitCode> {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("plaque: {message}\n{}", args::USAGE);
+            return Ok(ExitCode::from(headless::EXIT_USAGE_ERROR));
+        }
     };
 
-    program.read_stdin();
+    match args.command {
+        Command::Edit => {
+            let mut program = match args.filepath {
+                Some(filepath) => Program::load(filepath)?,
+                None => Program::blank(),
+            };
+            program.configure(args.config);
+            if let Err(code) = use_input(&mut program, &args.inputs) {
+                return Ok(code);
+            }
+
+            app::run(program)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Run | Command::Profile | Command::Coverage | Command::Lint => {
+            let filepath = args.filepath.unwrap_or_default();
+            let mut program = match Program::load(&filepath) {
+                Ok(program) => program,
+                Err(e) => {
+                    eprintln!("plaque: {filepath}: {e}");
+                    return Ok(ExitCode::from(headless::EXIT_USAGE_ERROR));
+                }
+            };
+            program.configure(args.config);
+            match args.command {
+                Command::Coverage if args.inputs.is_empty() => program.read_stdin(),
+                Command::Coverage | Command::Lint => {}
+                _ => {
+                    if let Err(code) = use_input(&mut program, &args.inputs) {
+                        return Ok(code);
+                    }
+                }
+            }
+
+            Ok(match args.command {
+                Command::Profile => headless::profile(&mut program),
+                Command::Coverage => headless::coverage(&mut program, &args.inputs),
+                Command::Lint => headless::lint(&program, args.format),
+                _ => headless::run(&mut program),
+            })
+        }
+    }
+}
 
-    app::run(program)
+/// Reads input from the given file, or from stdin when there isn't one
+fn use_input(program: &mut Program, inputs: &[String]) -> Result<(), ExitCode> {
+    match inputs.first() {
+        Some(path) => program.set_input_file(path).map_err(|message| {
+            eprintln!("plaque: {message}");
+            ExitCode::from(headless::EXIT_USAGE_ERROR)
+        }),
+        None => {
+            program.read_stdin();
+            Ok(())
+        }
+    }
 }
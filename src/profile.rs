@@ -0,0 +1,118 @@
+use crate::engine::Engine;
+use crate::instruction::Instruction;
+use crate::program::Program;
+
+use std::fmt::Write;
+
+/// Number of loops listed in a report
+const REPORT_LOOPS: usize = 10;
+
+/// Execution counts for a loop, from its `[` to its `]` inclusive
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LoopProfile {
+    pub open: usize,
+    pub close: usize,
+    /// Steps spent in the loop, including nested loops
+    pub steps: u64,
+    /// Times the body ran to its `]`, leaving out the `]` that follows skipping the loop
+    pub iterations: u64,
+}
+
+/// Loops that have run, with those that took the most steps first
+pub fn hottest_loops(engine: &Engine) -> Vec<LoopProfile> {
+    let mut loops = engine
+        .jumps
+        .iter()
+        .enumerate()
+        .filter(|&(open, _)| engine.instructions[open] == Instruction::JumpForward)
+        .filter_map(|(open, &close)| {
+            let close = close?;
+            Some(LoopProfile {
+                open,
+                close,
+                steps: engine.profile[open..=close].iter().sum(),
+                iterations: engine.profile[close].saturating_sub(engine.loop_skips[open]),
+            })
+        })
+        .filter(|profile| profile.steps > 0)
+        .collect::<Vec<_>>();
+
+    // ties go to the outer loop, which comes first
+    loops.sort_by(|a, b| b.steps.cmp(&a.steps).then(a.open.cmp(&b.open)));
+    loops
+}
+
+/// A plain text summary of where a program's steps went
+pub fn report(program: &Program) -> String {
+    let total: u64 = program.engine.profile.iter().sum();
+    let position = |i: usize| {
+        let (line, column) = program.instruction_positions[i];
+        format!("{}:{}", line + 1, column + 1)
+    };
+
+    let mut report = format!("total steps: {total}\n");
+    let loops = hottest_loops(&program.engine);
+    if loops.is_empty() {
+        return report;
+    }
+
+    writeln!(
+        report,
+        "\nhottest loops:\n{:>12} {:>6} {:>12}  location",
+        "steps", "share", "iterations"
+    )
+    .unwrap();
+    for profile in loops.iter().take(REPORT_LOOPS) {
+        writeln!(
+            report,
+            "{:>12} {:>5.1}% {:>12}  {}-{}",
+            profile.steps,
+            profile.steps as f64 * 100.0 / total as f64,
+            profile.iterations,
+            position(profile.open),
+            position(profile.close)
+        )
+        .unwrap();
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless;
+
+    #[test]
+    fn ranks_loops_by_steps() {
+        let mut program = Program::new();
+        program.load_source("++[>+++[>+<-]<-]\n+[-]");
+        headless::execute(&mut program, &mut &[][..], &mut vec![]).unwrap();
+
+        let loops = hottest_loops(&program.engine);
+        let spans = loops.iter().map(|l| (l.open, l.close)).collect::<Vec<_>>();
+        assert_eq!(spans, vec![(2, 15), (7, 12), (17, 19)]);
+        assert_eq!(loops[0].iterations, 2);
+        assert_eq!(loops[1].iterations, 6);
+        assert_eq!(loops[2].steps, 3);
+
+        let report = report(&program);
+        assert!(report.starts_with(&format!("total steps: {}\n", program.engine.history.len)));
+        assert!(report.contains("  1:3-1:16\n"));
+        assert!(report.contains("  2:2-2:4\n"));
+    }
+
+    #[test]
+    fn skipped_loops_have_no_iterations() {
+        let mut program = Program::new();
+        program.load_source("[-]++[>[-]+<-]");
+        headless::execute(&mut program, &mut &[][..], &mut vec![]).unwrap();
+
+        let loops = hottest_loops(&program.engine);
+        let iterations = loops
+            .iter()
+            .map(|l| (l.open, l.iterations))
+            .collect::<Vec<_>>();
+        assert_eq!(iterations, vec![(5, 2), (7, 1), (0, 0)]);
+    }
+}
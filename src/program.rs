@@ -1,26 +1,145 @@
-use crate::editor::Editor;
-use crate::engine::{Engine, EngineResult, Exception, InstructionPointer};
-use crate::instruction::Instruction;
+use crate::breakpoint::Watchpoint;
+use crate::config::Config;
+use crate::coverage;
+use crate::editor::{self, Editor};
+use crate::engine::{Engine, EngineResult, Exception, InstructionPointer, LoopFrame};
+use crate::instruction::Instruction::{self, *};
+use crate::lint::{self, Finding, Lint};
+use crate::search::{self, Search};
 
+use std::collections::HashMap;
+use std::fmt;
 use std::io::{self, Read};
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use tap::prelude::*;
+use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Mode {
     Editor,
     Input,
+    Running,
+    Playing,
+    Prompt(Prompt),
 }
 
+/// Single line text entries, shown in place of the title bar
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Prompt {
+    Condition,
+    Watch,
+    CoverageFile,
+    Cell,
+    CellValue,
+    InputFile,
+    OutputFile,
+    Search,
+    InstructionSearch,
+    Replace,
+    /// Asks whether to replace the match at the cursor, rather than taking text
+    ConfirmReplace,
+}
+
+impl Prompt {
+    pub fn label(self) -> &'static str {
+        match self {
+            Prompt::Condition => "Break when",
+            Prompt::Watch => "Watch cell",
+            Prompt::CoverageFile => "Save coverage to",
+            Prompt::Cell => "Go to cell",
+            Prompt::CellValue => "Set cell to",
+            Prompt::InputFile => "Load input from",
+            Prompt::OutputFile => "Save output to",
+            Prompt::Search => "Search",
+            Prompt::InstructionSearch => "Search instructions",
+            Prompt::Replace => "Replace with",
+            Prompt::ConfirmReplace => "Replace? (y)es, (n)o, (a)ll",
+        }
+    }
+}
+
+/// How tape cells are shown
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CellDisplay {
+    #[default]
+    Decimal,
+    Hex,
+    /// Two's complement at the cell width
+    Signed,
+    /// Printable ASCII characters, with escapes for anything else
+    Ascii,
+    Binary,
+}
+
+impl CellDisplay {
+    pub fn next(self) -> CellDisplay {
+        match self {
+            CellDisplay::Decimal => CellDisplay::Hex,
+            CellDisplay::Hex => CellDisplay::Signed,
+            CellDisplay::Signed => CellDisplay::Ascii,
+            CellDisplay::Ascii => CellDisplay::Binary,
+            CellDisplay::Binary => CellDisplay::Decimal,
+        }
+    }
+}
+
+impl fmt::Display for CellDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CellDisplay::Decimal => "decimal",
+            CellDisplay::Hex => "hex",
+            CellDisplay::Signed => "signed",
+            CellDisplay::Ascii => "ascii",
+            CellDisplay::Binary => "binary",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Play speeds in steps per second
+pub const PLAY_SPEEDS: [usize; 12] = [1, 2, 5, 10, 20, 50, 100, 200, 500, 1000, 10_000, 100_000];
+
 #[derive(Debug)]
 pub struct Program {
     pub engine: Engine,
     pub editor: Editor,
     pub instruction_positions: Vec<(usize, usize)>,
+    /// (lines, characters) between each instruction and the end of the source, which
+    /// don't change when editing earlier text
+    pub instruction_end_offsets: Vec<(usize, usize)>,
     pub mode: Mode,
     pub input_buffer: Vec<u8>,
+    pub prompt_text: String,
     pub stdin: Option<Vec<u8>>,
+    /// File the input is loaded from on reset, in place of stdin
+    pub input_file: Option<PathBuf>,
     pub debug_messages: Vec<String>,
+    /// Index into `PLAY_SPEEDS`
+    pub play_speed: usize,
+    pub play_backwards: bool,
+    /// Fractional steps carried over between play ticks
+    play_credit: f64,
+    /// Where the current run stops, when it is stepping over or out of a loop or running to
+    /// the cursor
+    run_target: Option<InstructionPointer>,
+    /// Shade the source by how often each instruction has run
+    pub show_profile: bool,
+    /// Dim instructions that have never run
+    pub show_coverage: bool,
+    pub cell_display: CellDisplay,
+    /// Cell the tape panel is centred on, or `None` to follow the tape pointer
+    pub tape_view: Option<isize>,
+    /// Number of cells the tape panel shows at once, as of the last draw
+    pub tape_page: usize,
+    pub show_output_hex: bool,
+    /// Page of the editor help being shown, wrapped to the number of pages when drawn
+    pub help_page: usize,
+    /// A backslash escape being typed in input mode
+    pub input_escape: String,
+    pub search: Search,
+    /// Lint findings for the current source
+    pub lints: Vec<Finding>,
 }
 
 impl Program {
@@ -29,10 +148,27 @@ impl Program {
             engine: Engine::new(vec![]),
             editor: Editor::new(),
             instruction_positions: vec![],
+            instruction_end_offsets: vec![],
             mode: Mode::Editor,
             input_buffer: vec![],
+            prompt_text: String::new(),
             stdin: None,
+            input_file: None,
             debug_messages: vec![],
+            play_speed: 3,
+            play_backwards: false,
+            play_credit: 0.0,
+            run_target: None,
+            show_profile: false,
+            show_coverage: false,
+            cell_display: CellDisplay::default(),
+            tape_view: None,
+            tape_page: 1,
+            show_output_hex: false,
+            help_page: 0,
+            input_escape: String::new(),
+            search: Search::default(),
+            lints: vec![],
         }
     }
 
@@ -59,35 +195,494 @@ impl Program {
             .as_ref()
             .ok_or(io::ErrorKind::NotFound)?;
         let code_text = std::fs::read_to_string(path)?;
+        self.load_source(&code_text);
+
+        Ok(())
+    }
+
+    pub fn load_source(&mut self, code_text: &str) {
         self.editor.lines = code_text
             .lines()
             .map(|line| line.to_string())
             .collect::<Vec<String>>();
+        self.editor.clear_history();
         self.index_instructions();
-
-        Ok(())
     }
 
     pub fn index_instructions(&mut self) {
-        self.engine.instructions = vec![];
-        self.instruction_positions = vec![];
+        let unmatched_positions = self.unmatched_jump_positions();
+        let old_instructions = std::mem::take(&mut self.engine.instructions);
+        let old_positions = std::mem::take(&mut self.instruction_positions);
+        let old_end_offsets = std::mem::take(&mut self.instruction_end_offsets);
+        let mut instructions = vec![];
 
+        let line_count = self.editor.lines.len();
         for (line_number, line) in self.editor.lines.iter().enumerate() {
-            for (column_number, character) in line.chars().enumerate() {
-                if let Some(instruction) = Instruction::read(character) {
-                    self.engine.instructions.push(instruction);
+            let line_length = editor::columns(line);
+            // columns are graphemes, so only a lone instruction character counts
+            for (column_number, grapheme) in line.graphemes(true).enumerate() {
+                let mut chars = grapheme.chars();
+                let character = chars.next().filter(|_| chars.next().is_none());
+                if let Some(instruction) = character.and_then(Instruction::read) {
+                    instructions.push(instruction);
                     self.instruction_positions
                         .push((line_number, column_number));
+                    self.instruction_end_offsets.push((
+                        line_count - line_number - 1,
+                        line_length - column_number - 1,
+                    ));
+                }
+            }
+        }
+
+        let remap = EditRemap::new(
+            (&old_instructions, &old_positions, &old_end_offsets),
+            (
+                &instructions,
+                &self.instruction_positions,
+                &self.instruction_end_offsets,
+            ),
+        );
+        self.engine.breakpoints = std::mem::take(&mut self.engine.breakpoints)
+            .into_iter()
+            .filter_map(|(i, condition)| Some((remap.index(i)?, condition)))
+            .collect();
+        self.engine.loop_stack = std::mem::take(&mut self.engine.loop_stack)
+            .into_iter()
+            .filter_map(|frame| {
+                Some(LoopFrame {
+                    open: remap.index(frame.open)?,
+                    ..frame
+                })
+            })
+            .collect();
+        let old_lints = self
+            .lints
+            .iter()
+            .map(|finding| (finding.lint, remap.index(finding.start)))
+            .collect::<Vec<_>>();
+        self.engine.profile = remap.values(&self.engine.profile, instructions.len());
+        self.engine.loop_skips = remap.values(&self.engine.loop_skips, instructions.len());
+        self.engine.coverage = remap.values(&self.engine.coverage, instructions.len());
+
+        self.engine.load_instructions(instructions);
+
+        // only report unmatched jumps when they change, rather than on every keystroke
+        if self.unmatched_jump_positions() != unmatched_positions {
+            for message in self.unmatched_jump_messages() {
+                self.debug_messages.push(message);
+            }
+        }
+
+        // likewise only report lints that the edit introduced, with unmatched jumps already
+        // reported above
+        self.lints = lint::check(&self.engine);
+        for finding in &self.lints {
+            let known = old_lints.contains(&(finding.lint, Some(finding.start)));
+            if finding.lint != Lint::UnmatchedBracket && !known {
+                let (line, column) = self.instruction_positions[finding.start];
+                self.debug_messages.push(format!(
+                    "{}: {} at {}:{}",
+                    finding.lint.severity(),
+                    finding.message,
+                    line + 1,
+                    column + 1
+                ));
+            }
+        }
+
+        self.engine.clamp_instruction_pointer();
+    }
+
+    /// The instruction under the editor cursor, or the next one on the line
+    pub fn instruction_at_cursor(&self) -> Option<usize> {
+        let (row, col) = self.editor.cursor;
+        self.instruction_positions
+            .iter()
+            .position(|&(line, column)| line == row && column >= col)
+    }
+
+    /// The instruction at a source position, if there is one
+    pub fn instruction_index_at(&self, position: (usize, usize)) -> Option<usize> {
+        self.instruction_positions.binary_search(&position).ok()
+    }
+
+    /// The bracket under the editor cursor, or just before it, with the position of its
+    /// partner if it has one
+    pub fn bracket_at_cursor(&self) -> Option<BracketPair> {
+        let (row, col) = self.editor.cursor;
+        let before = col.checked_sub(1).map(|col| (row, col));
+        let i = [Some((row, col)), before]
+            .into_iter()
+            .flatten()
+            .filter_map(|position| self.instruction_index_at(position))
+            .find(|&i| matches!(self.engine.instructions[i], JumpForward | JumpBackward))?;
+        let partner = self.engine.jumps[i].map(|j| self.instruction_positions[j]);
+
+        Some((self.instruction_positions[i], partner))
+    }
+
+    /// Move the editor cursor to the partner of the bracket at the cursor
+    pub fn jump_to_matching_bracket(&mut self) {
+        let Some((position, partner)) = self.bracket_at_cursor() else {
+            return;
+        };
+        match partner {
+            Some((row, col)) => {
+                self.editor.selection = None;
+                self.editor.set_pinned_cursor(row, col);
+                self.editor.scroll_into_view();
+            }
+            None => self.debug_messages.push(format!(
+                "no match for the bracket at {}:{}",
+                position.0 + 1,
+                position.1 + 1
+            )),
+        }
+    }
+
+    pub fn toggle_breakpoint(&mut self) {
+        match self.instruction_at_cursor() {
+            Some(i) if self.engine.breakpoints.remove(&i).is_some() => {}
+            Some(i) => {
+                self.engine.breakpoints.insert(i, None);
+            }
+            None => self
+                .debug_messages
+                .push("no instruction to break on at the cursor".to_string()),
+        }
+    }
+
+    /// Sets the condition of the breakpoint at the cursor, adding the breakpoint if needed.
+    /// An empty condition makes the breakpoint unconditional.
+    pub fn set_breakpoint_condition(&mut self, text: &str) -> Result<(), String> {
+        let i = self
+            .instruction_at_cursor()
+            .ok_or("no instruction to break on at the cursor")?;
+        let condition = match text.trim() {
+            "" => None,
+            text => Some(text.parse()?),
+        };
+        self.engine.breakpoints.insert(i, condition);
+        Ok(())
+    }
+
+    /// Adds a watchpoint, or removes it if the cell is already watched
+    pub fn toggle_watchpoint(&mut self, text: &str) -> Result<(), String> {
+        let watchpoint: Watchpoint = text.parse()?;
+        let watchpoints = &mut self.engine.watchpoints;
+
+        match watchpoints.iter().position(|w| w.cell == watchpoint.cell) {
+            Some(i) => {
+                watchpoints.remove(i);
+                self.debug_messages
+                    .push(format!("stopped watching cell {}", watchpoint.cell));
+            }
+            None => watchpoints.push(watchpoint),
+        }
+        Ok(())
+    }
+
+    pub fn open_prompt(&mut self, prompt: Prompt) {
+        if !self.is_editor_mode() {
+            return;
+        }
+
+        self.prompt_text = match prompt {
+            Prompt::Condition => self
+                .instruction_at_cursor()
+                .and_then(|i| self.engine.breakpoints.get(&i).cloned().flatten())
+                .map(|condition| condition.to_string())
+                .unwrap_or_default(),
+            Prompt::Watch | Prompt::Cell | Prompt::OutputFile | Prompt::ConfirmReplace => {
+                String::new()
+            }
+            Prompt::Search | Prompt::InstructionSearch => self.search.query.clone(),
+            Prompt::Replace => self.search.replacement.clone(),
+            Prompt::InputFile => self
+                .input_file
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+            Prompt::CellValue => self
+                .engine
+                .cell_at(self.tape_view_cell())
+                .unwrap_or(0)
+                .to_string(),
+            Prompt::CoverageFile => self
+                .editor
+                .filepath
+                .as_ref()
+                .map(|path| format!("{}.lcov", path.display()))
+                .unwrap_or_else(|| "coverage.lcov".to_string()),
+        };
+        self.mode = Mode::Prompt(prompt);
+
+        if self.is_searching() {
+            self.search.origin = (self.editor.cursor, self.editor.selection);
+            self.update_search();
+        }
+    }
+
+    pub fn submit_prompt(&mut self) {
+        let Mode::Prompt(prompt) = self.mode else {
+            return;
+        };
+        self.mode = Mode::Editor;
+
+        let text = std::mem::take(&mut self.prompt_text);
+        let result = match prompt {
+            Prompt::Condition => self.set_breakpoint_condition(&text),
+            Prompt::Watch => self.toggle_watchpoint(&text),
+            Prompt::CoverageFile => self.save_coverage(&text),
+            Prompt::Cell => self.view_cell(&text),
+            Prompt::CellValue => self.set_viewed_cell(&text),
+            Prompt::InputFile => self.load_input(&text),
+            Prompt::OutputFile => self.save_output(&text),
+            // confirming a replacement has its own keys, and never submits
+            Prompt::Search | Prompt::InstructionSearch | Prompt::ConfirmReplace => Ok(()),
+            Prompt::Replace => self.start_replace(&text),
+        };
+        if let Err(message) = result {
+            self.debug_messages.push(message);
+        }
+    }
+
+    pub fn cancel_prompt(&mut self) {
+        if self.is_searching() {
+            let (cursor, selection) = self.search.origin;
+            self.editor.set_pinned_cursor(cursor.0, cursor.1);
+            self.editor.selection = selection;
+            self.editor.scroll_into_view();
+        }
+        if self.mode == Mode::Prompt(Prompt::ConfirmReplace) {
+            return self.finish_replace();
+        }
+
+        self.mode = Mode::Editor;
+        self.prompt_text.clear();
+    }
+
+    pub fn type_prompt(&mut self, c: char) {
+        self.prompt_text.push(c);
+        self.update_search();
+    }
+
+    pub fn erase_prompt(&mut self) {
+        self.prompt_text.pop();
+        self.update_search();
+    }
+
+    fn is_searching(&self) -> bool {
+        matches!(
+            self.mode,
+            Mode::Prompt(Prompt::Search | Prompt::InstructionSearch)
+        )
+    }
+
+    /// Switch the open search between matching text and matching only instructions
+    pub fn toggle_instruction_search(&mut self) {
+        self.mode = match self.mode {
+            Mode::Prompt(Prompt::Search) => Mode::Prompt(Prompt::InstructionSearch),
+            Mode::Prompt(Prompt::InstructionSearch) => Mode::Prompt(Prompt::Search),
+            mode => mode,
+        };
+        self.update_search();
+    }
+
+    fn find_matches(&mut self) {
+        self.search.matches = if self.search.instructions_only {
+            search::find_instructions(
+                &self.engine.instructions,
+                &self.instruction_positions,
+                &self.search.query,
+            )
+        } else {
+            search::find_text(&self.editor.lines, &self.search.query)
+        };
+        self.search.current = None;
+    }
+
+    /// Follow the search prompt as it's typed, selecting the first match from where the
+    /// search started
+    fn update_search(&mut self) {
+        let Mode::Prompt(prompt @ (Prompt::Search | Prompt::InstructionSearch)) = self.mode else {
+            return;
+        };
+        self.search.query = self.prompt_text.clone();
+        self.search.instructions_only = prompt == Prompt::InstructionSearch;
+        self.find_matches();
+
+        let (cursor, selection) = self.search.origin;
+        match self.search.find_from(cursor, false) {
+            Some((i, _)) => self.go_to_match(i),
+            None => {
+                self.editor.set_pinned_cursor(cursor.0, cursor.1);
+                self.editor.selection = selection;
+            }
+        }
+    }
+
+    /// Select a match, with the cursor at its end
+    fn go_to_match(&mut self, i: usize) {
+        let found = &self.search.matches[i];
+        let ((row, col), start) = (found.end(), found.start());
+        self.search.current = Some(i);
+        self.editor.selection = Some(start);
+        self.editor.set_pinned_cursor(row, col);
+        self.editor.scroll_into_view();
+    }
+
+    /// Where the selection starts, or the cursor if nothing is selected
+    fn selection_start(&self) -> (usize, usize) {
+        let cursor = self.editor.cursor;
+        self.editor
+            .selection
+            .map_or(cursor, |selection| std::cmp::min(selection, cursor))
+    }
+
+    /// Move to the next or previous match of the last search, wrapping around the source
+    pub fn search_next(&mut self, backward: bool) {
+        self.find_matches();
+        let from = if backward {
+            self.selection_start()
+        } else {
+            self.editor.cursor
+        };
+
+        match self.search.find_from(from, backward) {
+            Some((i, wrapped)) => {
+                if wrapped {
+                    self.debug_messages
+                        .push("search wrapped around".to_string());
                 }
+                self.go_to_match(i);
             }
+            None => self
+                .debug_messages
+                .push(format!("no matches for {:?}", self.search.query)),
+        }
+    }
+
+    /// Replace matches of the last search one at a time, starting from the cursor
+    fn start_replace(&mut self, replacement: &str) -> Result<(), String> {
+        if self.search.query.is_empty() {
+            return Err("search for something to replace first".to_string());
+        }
+        self.search.replacement = replacement.to_string();
+        self.search.replaced = 0;
+        self.mode = Mode::Prompt(Prompt::ConfirmReplace);
+        self.next_replacement(self.selection_start());
+        Ok(())
+    }
+
+    /// Move on to the next match to replace, or finish if there are no more
+    fn next_replacement(&mut self, from: (usize, usize)) {
+        self.find_matches();
+        // replacing doesn't wrap, so text that was just replaced in isn't matched again
+        match self.search.matches.iter().position(|m| m.start() >= from) {
+            Some(i) => self.go_to_match(i),
+            None => self.finish_replace(),
+        }
+    }
+
+    pub fn replace_match(&mut self) {
+        let Some(i) = self.search.current else {
+            return;
+        };
+        let cells = self.search.matches[i].cells.clone();
+        self.editor.replace(&[cells], &self.search.replacement);
+        self.index_instructions();
+        self.search.replaced += 1;
+        self.next_replacement(self.editor.cursor);
+    }
+
+    pub fn skip_match(&mut self) {
+        let Some(i) = self.search.current else {
+            return;
+        };
+        self.next_replacement(self.search.matches[i].end());
+    }
+
+    /// Replace every match in the source, as a single edit
+    pub fn replace_all(&mut self) {
+        self.find_matches();
+        let matches = self
+            .search
+            .matches
+            .iter()
+            .map(|m| m.cells.clone())
+            .collect::<Vec<_>>();
+        self.editor.replace(&matches, &self.search.replacement);
+        self.index_instructions();
+        self.search.replaced += matches.len();
+        self.finish_replace();
+    }
+
+    fn finish_replace(&mut self) {
+        self.mode = Mode::Editor;
+        self.search.current = None;
+        self.debug_messages.push(format!(
+            "replaced {} match{}",
+            self.search.replaced,
+            if self.search.replaced == 1 { "" } else { "es" }
+        ));
+    }
+
+    /// Cells covered by matches while searching or replacing, mapped to whether they're
+    /// part of the match at the cursor
+    pub fn search_highlights(&self) -> HashMap<(usize, usize), bool> {
+        let replacing = self.mode == Mode::Prompt(Prompt::ConfirmReplace);
+        if !self.is_searching() && !replacing {
+            return HashMap::new();
         }
 
-        if self.engine.instructions.is_empty() {
-            self.engine.instruction_pointer = InstructionPointer::Start;
-        } else if let InstructionPointer::Index(i) = self.engine.instruction_pointer {
-            self.engine.instruction_pointer =
-                InstructionPointer::Index(std::cmp::min(i, self.instruction_positions.len() - 1));
+        let mut highlights = HashMap::new();
+        for (i, found) in self.search.matches.iter().enumerate() {
+            let current = self.search.current == Some(i);
+            highlights.extend(found.cells.iter().map(|&cell| (cell, current)));
         }
+        highlights
+    }
+
+    pub fn breakpoint_at(&self, position: (usize, usize)) -> bool {
+        self.engine
+            .breakpoints
+            .keys()
+            .any(|&i| self.instruction_positions.get(i) == Some(&position))
+    }
+
+    /// The breakpoints on a line, as whether each one is conditional
+    pub fn breakpoints_on_line(&self, line: usize) -> impl Iterator<Item = bool> + '_ {
+        self.engine
+            .breakpoints
+            .iter()
+            .filter(move |(&i, _)| self.instruction_positions.get(i).map(|p| p.0) == Some(line))
+            .map(|(_, condition)| condition.is_some())
+    }
+
+    pub fn unmatched_jump_positions(&self) -> Vec<(usize, usize)> {
+        self.engine
+            .unmatched_jumps
+            .iter()
+            .filter_map(|&i| self.instruction_positions.get(i).copied())
+            .collect()
+    }
+
+    pub fn unmatched_jump_messages(&self) -> Vec<String> {
+        self.engine
+            .unmatched_jumps
+            .iter()
+            .map(|&i| {
+                let (line, column) = self.instruction_positions[i];
+                format!(
+                    "unmatched '{}' at {}:{}",
+                    self.engine.instructions[i].symbol(),
+                    line + 1,
+                    column + 1
+                )
+            })
+            .collect()
     }
 
     pub fn step(&mut self) -> EngineResult {
@@ -98,7 +693,11 @@ impl Program {
             Exception::RequestingInput => {
                 self.enter_input_mode();
             }
-            Exception::Breakpoint => {}
+            Exception::Breakpoint => {
+                if let Some(reason) = self.engine.triggered.take() {
+                    self.debug_messages.push(reason);
+                }
+            }
         })
     }
 
@@ -110,30 +709,396 @@ impl Program {
         })
     }
 
-    pub fn step_until_exception(&mut self) {
-        loop {
-            if self.step().is_err() {
+    /// Starts stepping until an exception. The run only advances through `run_batch`, so
+    /// whoever owns the program can release it between batches.
+    pub fn start_running(&mut self) {
+        self.run_until(None);
+    }
+
+    /// Starts running until an exception or until the instruction pointer reaches `target`
+    fn run_until(&mut self, target: Option<InstructionPointer>) {
+        if self.is_editor_mode() {
+            self.mode = Mode::Running;
+            self.run_target = target;
+        }
+    }
+
+    /// Runs a whole loop when in front of its `[`, otherwise steps
+    pub fn step_over(&mut self) {
+        match self.engine.instruction_pointer {
+            InstructionPointer::Index(open) if self.engine.instructions[open] == JumpForward => {
+                match self.engine.jumps[open] {
+                    Some(close) => self.run_until(Some(self.instruction_after(close))),
+                    None => {
+                        self.step().ok();
+                    }
+                }
+            }
+            _ => {
+                self.step().ok();
+            }
+        }
+    }
+
+    /// Runs until the innermost loop around the instruction pointer exits
+    pub fn step_out(&mut self) {
+        match self.enclosing_loop() {
+            Some((_, close)) => self.run_until(Some(self.instruction_after(close))),
+            None => self.debug_messages.push("not inside a loop".to_string()),
+        }
+    }
+
+    pub fn run_to_cursor(&mut self) {
+        match self.instruction_at_cursor() {
+            Some(i) => self.run_until(Some(InstructionPointer::Index(i))),
+            None => self
+                .debug_messages
+                .push("no instruction at the cursor".to_string()),
+        }
+    }
+
+    /// Undoes a whole loop when just past its `]`, otherwise undoes one step
+    pub fn undo_step_over(&mut self) {
+        let previous = match self.engine.instruction_pointer {
+            InstructionPointer::Index(i) if i > 0 => Some(i - 1),
+            InstructionPointer::End => self.engine.instructions.len().checked_sub(1),
+            _ => None,
+        };
+
+        // the instruction before can only have been reached by running it when it's a `]`
+        match previous.filter(|&i| self.engine.instructions[i] == JumpBackward) {
+            Some(close) if !self.engine.history.is_empty() => match self.engine.jumps[close] {
+                Some(open) => self.undo_to_loop_entry(open),
+                None => {
+                    self.undo().ok();
+                }
+            },
+            _ => {
+                self.undo().ok();
+            }
+        }
+    }
+
+    /// Undoes back to when the innermost loop around the instruction pointer was entered
+    pub fn undo_step_out(&mut self) {
+        match self.enclosing_loop() {
+            Some((open, _)) => self.undo_to_loop_entry(open),
+            None => self.debug_messages.push("not inside a loop".to_string()),
+        }
+    }
+
+    /// Undoes back to the last time the instruction under the cursor was reached
+    pub fn undo_to_cursor(&mut self) {
+        let Some(i) = self.instruction_at_cursor() else {
+            self.debug_messages
+                .push("no instruction at the cursor".to_string());
+            return;
+        };
+
+        let target = InstructionPointer::Index(i);
+        match self
+            .engine
+            .rewind_until(|engine, _| engine.instruction_pointer == target)
+        {
+            Ok(true) => {}
+            Ok(false) if self.engine.instruction_pointer == target => {}
+            Ok(false) => self
+                .debug_messages
+                .push("the cursor wasn't reached, rewound to the start".to_string()),
+            Err(Exception::Error(message)) => self.debug_messages.push(message),
+            Err(_) => {}
+        }
+    }
+
+    fn undo_to_loop_entry(&mut self, open: usize) {
+        if let Err(Exception::Error(message)) = self.engine.rewind_to_loop_entry(open) {
+            self.debug_messages.push(message);
+        }
+    }
+
+    fn enclosing_loop(&self) -> Option<(usize, usize)> {
+        match self.engine.instruction_pointer {
+            InstructionPointer::Index(i) => self.engine.enclosing_loop(i),
+            _ => None,
+        }
+    }
+
+    fn instruction_after(&self, i: usize) -> InstructionPointer {
+        if i + 1 < self.engine.instructions.len() {
+            InstructionPointer::Index(i + 1)
+        } else {
+            InstructionPointer::End
+        }
+    }
+
+    /// Steps for up to `duration` while running, returning whether the run should continue
+    pub fn run_batch(&mut self, duration: Duration) -> bool {
+        // checking the time every step is surprisingly expensive
+        const STEPS_PER_CHECK: usize = 1024;
+
+        let start = Instant::now();
+        while self.is_running_mode() {
+            for _ in 0..STEPS_PER_CHECK {
+                if self.step().is_err() {
+                    if self.is_running_mode() {
+                        self.mode = Mode::Editor;
+                    }
+                    return false;
+                }
+                if self.run_target == Some(self.engine.instruction_pointer) {
+                    self.mode = Mode::Editor;
+                    return false;
+                }
+            }
+            if start.elapsed() >= duration {
                 break;
             }
         }
+
+        self.is_running_mode()
+    }
+
+    pub fn stop_running(&mut self) {
+        if self.is_running_mode() {
+            self.mode = Mode::Editor;
+            self.debug_messages
+                .push(format!("stopped after {} steps", self.engine.history.len));
+        }
     }
 
     pub fn undo_until_exception(&mut self) {
-        loop {
-            if self.undo().is_err() {
-                break;
+        if let Err(Exception::Error(message)) = self.engine.rewind_to_breakpoint() {
+            self.debug_messages.push(message);
+        }
+    }
+
+    pub fn start_playing(&mut self) {
+        if self.is_editor_mode() {
+            self.mode = Mode::Playing;
+            self.play_credit = 0.0;
+        }
+    }
+
+    pub fn stop_playing(&mut self) {
+        if self.is_playing_mode() {
+            self.mode = Mode::Editor;
+        }
+    }
+
+    pub fn change_play_speed(&mut self, faster: bool) {
+        self.play_speed = if faster {
+            std::cmp::min(self.play_speed + 1, PLAY_SPEEDS.len() - 1)
+        } else {
+            self.play_speed.saturating_sub(1)
+        };
+    }
+
+    pub fn steps_per_second(&self) -> usize {
+        PLAY_SPEEDS[self.play_speed]
+    }
+
+    /// Steps (or undoes when playing backwards) as many times as the play speed allows in the
+    /// time since the last tick, pausing on any exception
+    pub fn play(&mut self, elapsed: Duration) {
+        if !self.is_playing_mode() {
+            return;
+        }
+
+        let steps_per_second = self.steps_per_second() as f64;
+        // don't try to catch up after a stall
+        self.play_credit = f64::min(
+            self.play_credit + elapsed.as_secs_f64() * steps_per_second,
+            f64::max(1.0, steps_per_second / 10.0),
+        );
+
+        while self.play_credit >= 1.0 && self.is_playing_mode() {
+            self.play_credit -= 1.0;
+            let result = if self.play_backwards {
+                self.undo()
+            } else {
+                self.step()
+            };
+            if result.is_err() {
+                self.stop_playing();
             }
         }
     }
 
     pub fn reset(&mut self) {
         self.engine.reset();
-        if let Some(stdin) = &self.stdin {
-            self.engine.input = stdin.clone();
+        match &self.input_file {
+            Some(path) => match std::fs::read(path) {
+                Ok(input) => self.engine.input = input,
+                // run with no input rather than whatever the last run left behind
+                Err(e) => {
+                    self.engine.input.clear();
+                    self.debug_messages
+                        .push(format!("failed to load input from {}: {e}", path.display()));
+                }
+            },
+            None => {
+                if let Some(stdin) = &self.stdin {
+                    self.engine.input = stdin.clone();
+                }
+            }
         }
         self.step().ok();
     }
 
+    /// Uses a file as the input from now on, reloading it on every reset
+    pub fn set_input_file(&mut self, path: &str) -> Result<(), String> {
+        self.engine.input =
+            std::fs::read(path).map_err(|e| format!("failed to load input from {path}: {e}"))?;
+        self.input_file = Some(PathBuf::from(path));
+        Ok(())
+    }
+
+    /// Replaces the pending input with the contents of a file, as an entry in history, and
+    /// remembers the file for resets. An empty path goes back to stdin.
+    pub fn load_input(&mut self, path: &str) -> Result<(), String> {
+        let path = path.trim();
+        if path.is_empty() {
+            self.input_file = None;
+            self.debug_messages
+                .push("input will come from stdin on reset".to_string());
+            return Ok(());
+        }
+
+        let input =
+            std::fs::read(path).map_err(|e| format!("failed to load input from {path}: {e}"))?;
+        self.engine.edit(|engine| engine.input = input);
+        self.input_file = Some(PathBuf::from(path));
+        self.debug_messages
+            .push(format!("loaded input from {path}"));
+        Ok(())
+    }
+
+    pub fn save_output(&mut self, path: &str) -> Result<(), String> {
+        let path = path.trim();
+        std::fs::write(path, &self.engine.output)
+            .map_err(|e| format!("failed to save output to {path}: {e}"))?;
+        self.debug_messages.push(format!("saved output to {path}"));
+        Ok(())
+    }
+
+    pub fn configure(&mut self, config: Config) {
+        self.engine.config = config;
+        self.lints = lint::check(&self.engine);
+        self.reset();
+    }
+
+    pub fn toggle_profile(&mut self) {
+        self.show_profile = !self.show_profile;
+    }
+
+    pub fn toggle_output_hex(&mut self) {
+        self.show_output_hex = !self.show_output_hex;
+    }
+
+    pub fn next_help_page(&mut self) {
+        self.help_page = self.help_page.wrapping_add(1);
+    }
+
+    pub fn toggle_coverage(&mut self) {
+        self.show_coverage = !self.show_coverage;
+    }
+
+    pub fn save_coverage(&mut self, path: &str) -> Result<(), String> {
+        std::fs::write(path, coverage::lcov(self))
+            .map_err(|e| format!("failed to save coverage to {path}: {e}"))?;
+        self.debug_messages
+            .push(format!("saved coverage to {path}"));
+        Ok(())
+    }
+
+    pub fn cycle_cell_display(&mut self) {
+        self.cell_display = self.cell_display.next();
+        self.debug_messages
+            .push(format!("tape display: {}", self.cell_display));
+    }
+
+    /// The cell the tape panel is centred on
+    pub fn tape_view_cell(&self) -> isize {
+        self.tape_view
+            .unwrap_or_else(|| self.engine.cell_index(self.engine.tape_pointer))
+    }
+
+    /// Moves the tape view by a number of cells, keeping within the tape
+    pub fn scroll_tape(&mut self, cells: isize) {
+        let range = self.engine.cell_range();
+        let cell = self.tape_view_cell().saturating_add(cells);
+        self.tape_view = Some(cell.clamp(*range.start(), *range.end()));
+    }
+
+    pub fn page_tape(&mut self, forwards: bool) {
+        let page = self.tape_page as isize;
+        self.scroll_tape(if forwards { page } else { -page });
+    }
+
+    pub fn follow_tape_pointer(&mut self) {
+        self.tape_view = None;
+    }
+
+    pub fn view_cell(&mut self, text: &str) -> Result<(), String> {
+        let cell = text
+            .trim()
+            .parse()
+            .map_err(|_| format!("expected a cell number but found '{}'", text.trim()))?;
+        if !self.engine.cell_range().contains(&cell) {
+            return Err(format!("cell {cell} is outside the tape"));
+        }
+        self.tape_view = Some(cell);
+        Ok(())
+    }
+
+    /// Sets the cell in the middle of the tape view, as an entry in history. Values can be
+    /// numbers, negative numbers that wrap to the cell width, hex with `0x` or a quoted character.
+    pub fn set_viewed_cell(&mut self, text: &str) -> Result<(), String> {
+        let text = text.trim();
+        let max = self.engine.config.cell_width.max() as i64;
+        let value = if let Some(hex) = text.strip_prefix("0x") {
+            i64::from_str_radix(hex, 16).ok()
+        } else if let Some(quoted) = text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+            let mut chars = quoted.chars();
+            chars
+                .next()
+                .filter(|_| chars.next().is_none())
+                .map(|c| c as i64)
+        } else {
+            text.parse().ok()
+        }
+        .filter(|value| (-(max + 1) / 2..=max).contains(value))
+        .ok_or_else(|| format!("expected a cell value but found '{text}'"))?;
+
+        // negative values wrap around like decrementing past zero
+        let value = value.rem_euclid(max + 1) as u32;
+        self.engine
+            .edit_cell(self.tape_view_cell(), value)
+            .map_err(Self::error_message)
+    }
+
+    /// Moves the tape pointer to the cell in the middle of the tape view, as an entry in history
+    pub fn move_pointer_to_view(&mut self) {
+        let result = self.engine.edit_tape_pointer(self.tape_view_cell());
+        match result {
+            Ok(()) => self.tape_view = None,
+            Err(e) => self.debug_messages.push(Self::error_message(e)),
+        }
+    }
+
+    fn error_message(exception: Exception) -> String {
+        match exception {
+            Exception::Error(message) => message,
+            other => format!("{other:?}"),
+        }
+    }
+
+    pub fn cycle_eof(&mut self) {
+        let eof = self.engine.config.eof.next();
+        self.engine.config.eof = eof;
+        self.debug_messages.push(format!("end of input: {eof}"));
+    }
+
     pub fn is_editor_mode(&self) -> bool {
         self.mode == Mode::Editor
     }
@@ -142,6 +1107,14 @@ impl Program {
         self.mode == Mode::Input
     }
 
+    pub fn is_running_mode(&self) -> bool {
+        self.mode == Mode::Running
+    }
+
+    pub fn is_playing_mode(&self) -> bool {
+        self.mode == Mode::Playing
+    }
+
     pub fn read_stdin(&mut self) {
         self.stdin = if atty::isnt(atty::Stream::Stdin) {
             let stdin = io::stdin()
@@ -161,17 +1134,79 @@ impl Program {
         self.input_buffer = self.engine.input.clone();
     }
 
+    /// Edits the pending input while paused
+    pub fn edit_input(&mut self) {
+        if self.is_editor_mode() {
+            self.enter_input_mode();
+        }
+    }
+
     pub fn exit_input_mode(&mut self, commit: bool) {
         self.mode = Mode::Editor;
-        if commit {
-            self.engine.input = self.input_buffer.clone();
+        self.input_escape.clear();
+        if commit && self.input_buffer != self.engine.input {
+            let input = self.input_buffer.clone();
+            self.engine.edit(|engine| engine.input = input);
         }
         self.input_buffer = vec![];
     }
 
+    /// Removes the last character typed, or the last byte when it isn't part of one
+    pub fn remove_input(&mut self) {
+        if self.input_escape.pop().is_some() {
+            return;
+        }
+
+        let len = self.input_buffer.len();
+        let tail = &self.input_buffer[len.saturating_sub(4)..];
+        let last = match tail.utf8_chunks().last() {
+            Some(chunk) if chunk.invalid().is_empty() => {
+                chunk.valid().chars().last().map_or(1, char::len_utf8)
+            }
+            _ => 1,
+        };
+        self.input_buffer.truncate(len.saturating_sub(last));
+    }
+
+    /// Adds a character as UTF-8. A backslash starts an escape, where `\xNN` adds the raw
+    /// byte NN and `\\` adds a backslash.
     pub fn add_input(&mut self, c: char) {
-        if c.is_ascii() {
-            self.input_buffer.push(c as u8);
+        if self.input_escape.is_empty() && c != '\\' {
+            let mut bytes = [0; 4];
+            self.input_buffer
+                .extend_from_slice(c.encode_utf8(&mut bytes).as_bytes());
+            return;
+        }
+
+        self.input_escape.push(c);
+        let escape = self.input_escape.as_str();
+        let byte = match escape {
+            "\\\\" => Some(b'\\'),
+            _ => escape
+                .strip_prefix("\\x")
+                .filter(|hex| hex.len() == 2)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+        };
+        let incomplete = match escape.strip_prefix("\\x") {
+            Some(hex) => hex.len() < 2 && hex.chars().all(|c| c.is_ascii_hexdigit()),
+            None => escape == "\\",
+        };
+
+        if let Some(byte) = byte {
+            self.input_buffer.push(byte);
+            self.input_escape.clear();
+        } else if !incomplete {
+            // not an escape after all, so keep what was typed
+            let escape = std::mem::take(&mut self.input_escape);
+            self.input_buffer.extend_from_slice(escape.as_bytes());
+        }
+    }
+
+    /// Adds the editor clipboard to the input
+    pub fn paste_input(&mut self) {
+        if let Some(lines) = &self.editor.clipboard {
+            let text = lines.join("\n");
+            self.input_buffer.extend_from_slice(text.as_bytes());
         }
     }
 
@@ -182,3 +1217,634 @@ impl Program {
         }
     }
 }
+
+/// The position of a bracket, and of its partner if it has one
+type BracketPair = ((usize, usize), Option<(usize, usize)>);
+
+type InstructionIndex<'a> = (
+    &'a [Instruction],
+    &'a [(usize, usize)],
+    &'a [(usize, usize)],
+);
+
+/// Maps instruction indices from before an edit to after it. Instructions before the edited
+/// text keep their positions, and instructions after it keep their offsets from the end.
+struct EditRemap {
+    prefix: usize,
+    suffix: usize,
+    old_len: usize,
+    new_len: usize,
+}
+
+impl EditRemap {
+    fn new(old: InstructionIndex, new: InstructionIndex) -> EditRemap {
+        let (old_len, new_len) = (old.0.len(), new.0.len());
+        let shortest = std::cmp::min(old_len, new_len);
+
+        let prefix = (0..shortest)
+            .take_while(|&i| old.0[i] == new.0[i] && old.1[i] == new.1[i])
+            .count();
+        let suffix = (1..=shortest - prefix)
+            .take_while(|&i| {
+                let (j, k) = (old_len - i, new_len - i);
+                old.0[j] == new.0[k] && old.2[j] == new.2[k]
+            })
+            .count();
+
+        EditRemap {
+            prefix,
+            suffix,
+            old_len,
+            new_len,
+        }
+    }
+
+    /// Moves per-instruction values to their new indices, dropping those of deleted
+    /// instructions and starting inserted ones from the default
+    fn values<T: Clone + Default>(&self, values: &[T], new_len: usize) -> Vec<T> {
+        let mut remapped = vec![T::default(); new_len];
+        for (i, value) in values.iter().enumerate() {
+            if let Some(i) = self.index(i) {
+                remapped[i] = value.clone();
+            }
+        }
+        remapped
+    }
+
+    /// The new index of an instruction, or None if the edit removed it
+    fn index(&self, i: usize) -> Option<usize> {
+        if i < self.prefix {
+            Some(i)
+        } else if i >= self.old_len - self.suffix && i < self.old_len {
+            Some(i + self.new_len - self.old_len)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TapeMode;
+
+    fn program(source: &str) -> Program {
+        let mut program = Program::new();
+        program.load_source(source);
+        program
+    }
+
+    #[test]
+    fn breakpoints_follow_edits() {
+        let mut program = program("+[->+<]\n>.");
+        program.editor.cursor = (1, 1);
+        program.toggle_breakpoint();
+        assert!(program.breakpoint_at((1, 1)));
+
+        // insert text before the breakpoint, on the same line and on a new line
+        program.editor.cursor = (1, 0);
+        program.editor.insert_char('+');
+        program.index_instructions();
+        assert!(program.breakpoint_at((1, 2)));
+
+        program.editor.cursor = (0, 0);
+        program.editor.newline();
+        program.index_instructions();
+        assert!(program.breakpoint_at((2, 2)));
+
+        // editing after the breakpoint leaves it alone
+        program.editor.cursor = (2, 3);
+        program.editor.insert_char('-');
+        program.index_instructions();
+        assert!(program.breakpoint_at((2, 2)));
+
+        // deleting the instruction deletes the breakpoint
+        program.editor.cursor = (2, 3);
+        program.editor.backward_delete();
+        program.index_instructions();
+        assert!(program.engine.breakpoints.is_empty());
+    }
+
+    #[test]
+    fn undoes_into_a_checkpoint_after_shrinking_the_source() {
+        let mut program = program("+++++");
+        for _ in 0..5 {
+            program.step().ok();
+        }
+        program.enter_input_mode();
+        program.add_input('a');
+        program.exit_input_mode(true);
+
+        // the checkpoint before the edit points past the end of the new program
+        program.load_source("+");
+        program.undo().unwrap();
+        assert_eq!(program.engine.history.len, 4);
+        assert_eq!(program.cursor(), Some((0, 0)));
+        program.step().ok();
+        assert_eq!(program.engine.tape, [5]);
+    }
+
+    #[test]
+    fn undoes_into_loops_that_no_longer_exist() {
+        let mut program = program("+[[[+]]]");
+        for _ in 0..6 {
+            program.step().ok();
+        }
+        assert_eq!(program.engine.loop_stack.len(), 3);
+        program.enter_input_mode();
+        program.add_input('a');
+        program.exit_input_mode(true);
+
+        program.load_source("+");
+        assert!(program.engine.loop_stack.is_empty());
+        program.undo().unwrap();
+        assert!(program.engine.loop_stack.is_empty());
+    }
+
+    #[test]
+    fn indexes_instructions_by_grapheme() {
+        // the `-` carries a combining mark, so it's a comment rather than an instruction
+        let program = program("\u{1F600} caf\u{E9}> +-\u{338}.");
+        assert_eq!(program.instruction_positions, vec![(0, 6), (0, 8), (0, 10)]);
+        assert_eq!(
+            program.engine.instructions,
+            vec![IncrementPointer, Increment, Output]
+        );
+    }
+
+    #[test]
+    fn searches_incrementally() {
+        let mut program = program("+>+ add\n>> +add\n+");
+        program.editor.cursor = (0, 4);
+        program.open_prompt(Prompt::Search);
+        program.type_prompt('a');
+        assert_eq!(program.editor.cursor, (0, 5));
+        program.type_prompt('d');
+        program.type_prompt('d');
+        assert_eq!(program.search.current, Some(0));
+
+        program.search_next(false);
+        assert_eq!(program.editor.selection, Some((1, 4)));
+        program.search_next(false);
+        assert_eq!(program.editor.selection, Some((0, 4)));
+        assert_eq!(
+            program.debug_messages.last().unwrap(),
+            "search wrapped around"
+        );
+
+        // cancelling goes back to where the search started
+        program.cancel_prompt();
+        assert_eq!(
+            (program.editor.cursor, program.editor.selection),
+            ((0, 4), None)
+        );
+        assert!(program.search_highlights().is_empty());
+
+        // matching instructions skips the comments between them
+        program.editor.cursor = (0, 1);
+        program.open_prompt(Prompt::Search);
+        program.toggle_instruction_search();
+        program.prompt_text = "+>>".to_string();
+        program.erase_prompt();
+        assert_eq!(program.mode, Mode::Prompt(Prompt::InstructionSearch));
+        assert_eq!(program.search.current, Some(1));
+        assert_eq!(program.search.matches[1].cells, vec![(0, 2), (1, 0)]);
+        program.submit_prompt();
+        assert_eq!(program.editor.selection, Some((0, 2)));
+        assert_eq!(program.editor.cursor, (1, 1));
+    }
+
+    #[test]
+    fn replaces_each_or_all_matches() {
+        let mut program = program("+ add + add\n+");
+        program.open_prompt(Prompt::Search);
+        "add".chars().for_each(|c| program.type_prompt(c));
+        program.submit_prompt();
+
+        // replacing picks up from the start of the selected match
+        program.open_prompt(Prompt::Replace);
+        program.prompt_text = "add add".to_string();
+        program.submit_prompt();
+        assert_eq!(program.mode, Mode::Prompt(Prompt::ConfirmReplace));
+        program.skip_match();
+        program.replace_match();
+        assert_eq!(program.editor.lines, vec!["+ add + add add", "+"]);
+        assert_eq!(program.mode, Mode::Editor);
+        assert_eq!(program.debug_messages.last().unwrap(), "replaced 1 match");
+
+        // an instruction search replaces the instructions alone, leaving comments in place
+        program.editor.cursor = (0, 0);
+        program.open_prompt(Prompt::InstructionSearch);
+        program.prompt_text.clear();
+        "++".chars().for_each(|c| program.type_prompt(c));
+        program.submit_prompt();
+        program.open_prompt(Prompt::Replace);
+        program.prompt_text = "-".to_string();
+        program.submit_prompt();
+        program.replace_all();
+        assert_eq!(program.editor.lines, vec!["- add  add add", "+"]);
+        assert_eq!(program.engine.instructions, vec![Decrement, Increment]);
+
+        program.editor.undo();
+        assert_eq!(program.editor.lines, vec!["+ add + add add", "+"]);
+    }
+
+    #[test]
+    fn jumps_between_matching_brackets() {
+        let mut program = program("+[>[-]\n<-] ]");
+        program.editor.cursor = (0, 1);
+        assert_eq!(program.bracket_at_cursor(), Some(((0, 1), Some((1, 2)))));
+        program.jump_to_matching_bracket();
+        assert_eq!(program.editor.cursor, (1, 2));
+
+        // a cursor just past a bracket counts as being on it
+        program.editor.cursor = (0, 6);
+        assert_eq!(program.bracket_at_cursor(), Some(((0, 5), Some((0, 3)))));
+        program.editor.cursor = (0, 2);
+        assert_eq!(program.bracket_at_cursor(), Some(((0, 1), Some((1, 2)))));
+
+        program.editor.cursor = (1, 4);
+        program.jump_to_matching_bracket();
+        assert_eq!(program.editor.cursor, (1, 4));
+        assert_eq!(
+            program.debug_messages.last().unwrap(),
+            "no match for the bracket at 2:5"
+        );
+    }
+
+    #[test]
+    fn reports_new_lints_once() {
+        let mut program = program("+-");
+        assert_eq!(
+            program.debug_messages,
+            vec!["warning: '+-' cancels itself out at 1:1"]
+        );
+
+        // moving a finding along doesn't report it again
+        program.editor.cursor = (0, 0);
+        program.editor.insert_char('>');
+        program.index_instructions();
+        assert_eq!(program.debug_messages.len(), 1);
+
+        program.editor.cursor = (0, 3);
+        program.editor.insert_char('[');
+        program.editor.insert_char(']');
+        program.index_instructions();
+        assert_eq!(
+            program.debug_messages.last().unwrap(),
+            "warning: loop never changes the cell it tests, so it can't end once entered at 1:4"
+        );
+    }
+
+    #[test]
+    fn prompts_set_conditions_and_watchpoints() {
+        let mut program = program("+[>+<+]");
+        program.editor.cursor = (0, 3);
+        program.open_prompt(Prompt::Condition);
+        program.prompt_text = "cell > 3".to_string();
+        program.submit_prompt();
+        assert!(program.is_editor_mode());
+        assert_eq!(
+            program.breakpoints_on_line(0).collect::<Vec<_>>(),
+            vec![true]
+        );
+
+        program.open_prompt(Prompt::Condition);
+        assert_eq!(program.prompt_text, "cell > 3");
+        program.cancel_prompt();
+
+        program.open_prompt(Prompt::Watch);
+        program.prompt_text = "nope".to_string();
+        program.submit_prompt();
+        assert!(program.engine.watchpoints.is_empty());
+        assert!(!program.debug_messages.is_empty());
+
+        program.toggle_watchpoint("1 write").unwrap();
+        program.toggle_watchpoint("1").unwrap();
+        assert!(program.engine.watchpoints.is_empty());
+    }
+
+    #[test]
+    fn breakpoints_stop_execution_before_the_instruction() {
+        let mut program = program("+++ >+ .");
+        program.editor.cursor = (0, 3);
+        program.toggle_breakpoint();
+        program.step().ok();
+
+        program.start_running();
+        while program.run_batch(Duration::MAX) {}
+        assert_eq!(program.cursor(), Some((0, 4)));
+        assert_eq!(program.engine.history.len, 3);
+
+        program.start_running();
+        while program.run_batch(Duration::MAX) {}
+        assert_eq!(program.engine.output, vec![1]);
+    }
+
+    #[test]
+    fn runs_in_batches_until_stopped() {
+        let mut program = program("+[]");
+        program.step().ok();
+        program.start_running();
+        // every batch takes at least one round of steps, however short
+        assert!(program.run_batch(Duration::ZERO));
+        assert!(program.run_batch(Duration::from_millis(1)));
+        assert!(program.is_running_mode());
+
+        let steps = program.engine.history.len;
+        assert!(steps >= 2048);
+        let messages = program.debug_messages.len();
+        program.stop_running();
+        assert!(program.is_editor_mode());
+        assert_eq!(
+            program.debug_messages.last().unwrap(),
+            &format!("stopped after {steps} steps")
+        );
+
+        // stopping when not running does nothing
+        program.stop_running();
+        assert_eq!(program.debug_messages.len(), messages + 1);
+        assert!(!program.run_batch(Duration::MAX));
+    }
+
+    #[test]
+    fn batches_end_on_exceptions() {
+        let mut program = program("+$+<");
+        program.step().ok();
+        program.start_running();
+        assert!(!program.run_batch(Duration::MAX));
+        assert!(program.is_editor_mode());
+        assert_eq!(program.cursor(), Some((0, 2)));
+
+        program.start_running();
+        assert!(!program.run_batch(Duration::MAX));
+        assert!(program.is_editor_mode());
+        assert_eq!(program.cursor(), Some((0, 3)));
+        assert_eq!(
+            program.debug_messages.last().unwrap(),
+            "can't move before the first cell (0)"
+        );
+    }
+
+    #[test]
+    fn plays_at_the_chosen_speed() {
+        let mut program = program(&"+".repeat(100));
+        // a sixteenth of a second is exact in floating point
+        let tick = Duration::from_secs_f64(1.0 / 16.0);
+        program.step().ok();
+
+        program.play_speed = 0;
+        program.start_playing();
+        program.play(tick * 8);
+        assert_eq!(program.engine.history.len, 0);
+        program.play(tick * 8);
+        assert_eq!(program.engine.history.len, 1);
+
+        program.play_speed = PLAY_SPEEDS.iter().position(|&speed| speed == 100).unwrap();
+        program.play(tick);
+        assert_eq!(program.engine.history.len, 7);
+        // the remainder carries over to the next tick
+        program.play(tick);
+        assert_eq!(program.engine.history.len, 13);
+
+        // a stall only catches up a tenth of a second
+        program.play(Duration::from_secs(10));
+        assert_eq!(program.engine.history.len, 23);
+
+        program.play_backwards = true;
+        program.play(Duration::from_secs(10));
+        assert_eq!(program.engine.history.len, 13);
+        assert_eq!(program.engine.tape, [13]);
+        assert!(program.is_playing_mode());
+    }
+
+    #[test]
+    fn playing_stops_on_an_exception() {
+        let mut program = program("++<+");
+        program.step().ok();
+        program.play_speed = PLAY_SPEEDS.len() - 1;
+        program.start_playing();
+        program.play(Duration::from_secs(1));
+        assert!(program.is_editor_mode());
+        assert_eq!(program.engine.history.len, 2);
+        assert_eq!(
+            program.debug_messages.last().unwrap(),
+            "can't move before the first cell (0)"
+        );
+
+        // undoing past the start is an exception too
+        program.play_backwards = true;
+        program.start_playing();
+        program.play(Duration::from_secs(1));
+        assert!(program.is_editor_mode());
+        assert_eq!(program.engine.history.len, 0);
+        assert_eq!(
+            program.debug_messages.last().unwrap(),
+            "no previous instruction to undo"
+        );
+    }
+
+    fn run(program: &mut Program) {
+        while program.run_batch(Duration::MAX) {}
+    }
+
+    #[test]
+    fn steps_over_and_out_of_loops() {
+        let mut program = program("++[>+[-]<-]>.");
+        for _ in 0..3 {
+            program.step().ok();
+        }
+
+        program.step_over();
+        run(&mut program);
+        assert_eq!(program.cursor(), Some((0, 11)));
+        assert_eq!(program.engine.tape, [0, 0]);
+
+        program.undo_step_over();
+        assert_eq!(program.cursor(), Some((0, 2)));
+        assert_eq!(program.engine.tape, [2, 0]);
+
+        for _ in 0..4 {
+            program.step().ok();
+        }
+        assert_eq!(program.cursor(), Some((0, 6)));
+        program.step_out();
+        run(&mut program);
+        assert_eq!(program.cursor(), Some((0, 8)));
+        assert_eq!(program.engine.tape, [2, 0]);
+
+        program.step().ok();
+        program.undo_step_out();
+        assert_eq!(program.cursor(), Some((0, 2)));
+        assert_eq!(program.engine.history.len, 2);
+
+        program.undo_step_out();
+        assert_eq!(program.debug_messages.last().unwrap(), "not inside a loop");
+    }
+
+    #[test]
+    fn runs_and_undoes_to_the_cursor() {
+        let mut program = program("+++[>+<-]>.");
+        program.step().ok();
+        program.editor.cursor = (0, 5);
+        program.run_to_cursor();
+        run(&mut program);
+        assert_eq!(program.cursor(), Some((0, 5)));
+        assert_eq!(program.engine.tape, [3, 0]);
+
+        for _ in 0..2 {
+            program.run_to_cursor();
+            run(&mut program);
+        }
+        assert_eq!(program.engine.tape, [1, 2]);
+
+        program.undo_to_cursor();
+        assert_eq!(program.cursor(), Some((0, 5)));
+        assert_eq!(program.engine.tape, [2, 1]);
+    }
+
+    #[test]
+    fn loop_stack_follows_edits() {
+        let mut program = program("+[>+<-]");
+        for _ in 0..4 {
+            program.step().ok();
+        }
+        assert_eq!(program.engine.loop_stack[0].open, 1);
+
+        program.editor.cursor = (0, 0);
+        program.editor.insert_char('+');
+        program.index_instructions();
+        assert_eq!(program.engine.loop_stack[0].open, 2);
+
+        program.editor.cursor = (0, 3);
+        program.editor.backward_delete();
+        program.index_instructions();
+        assert!(program.engine.loop_stack.is_empty());
+    }
+
+    #[test]
+    fn tape_view_scrolls_within_the_tape() {
+        let mut program = program(">>+");
+        program.tape_page = 10;
+        assert_eq!(program.tape_view_cell(), 0);
+
+        program.page_tape(true);
+        program.scroll_tape(-3);
+        assert_eq!(program.tape_view, Some(7));
+        program.page_tape(false);
+        assert_eq!(program.tape_view, Some(0));
+
+        assert!(program.view_cell("-1").is_err());
+        program.view_cell("500").unwrap();
+        assert_eq!(program.tape_view_cell(), 500);
+
+        program.engine.config.tape = TapeMode::Fixed;
+        program.engine.config.tape_size = 100;
+        program.scroll_tape(1);
+        assert_eq!(program.tape_view, Some(99));
+
+        program.follow_tape_pointer();
+        assert_eq!(program.tape_view_cell(), 0);
+    }
+
+    #[test]
+    fn tape_edits_are_undoable() {
+        let mut program = program("+.");
+        program.step().ok();
+
+        program.view_cell("4").unwrap();
+        program.set_viewed_cell("'A'").unwrap();
+        program.set_viewed_cell("-1").unwrap();
+        assert_eq!(program.engine.cell_at(4), Some(255));
+        assert!(program.set_viewed_cell("256").is_err());
+        assert!(program.set_viewed_cell("'AB'").is_err());
+
+        program.move_pointer_to_view();
+        assert_eq!(program.tape_view, None);
+        program.step().ok();
+        program.step().ok();
+        assert_eq!(program.engine.output, [0]);
+
+        for _ in 0..3 {
+            program.undo().ok();
+        }
+        assert_eq!(program.engine.cell_at(4), Some(255));
+        assert_eq!(program.engine.tape_pointer, 0);
+        program.undo().ok();
+        assert_eq!(program.engine.cell_at(4), Some(65));
+        program.undo().ok();
+        assert_eq!(program.engine.tape.len(), 1);
+    }
+
+    #[test]
+    fn input_accepts_unicode_and_escapes() {
+        let mut program = program(",");
+        program.edit_input();
+        for c in "h\u{E9}\\x00\\xff\\\\\\q\u{1F600}".chars() {
+            program.add_input(c);
+        }
+        assert_eq!(
+            program.input_buffer,
+            b"h\xC3\xA9\x00\xFF\\\\q\xF0\x9F\x98\x80"
+        );
+
+        program.remove_input();
+        assert_eq!(program.input_buffer, b"h\xC3\xA9\x00\xFF\\\\q");
+        program.add_input('\\');
+        program.add_input('x');
+        // the unfinished escape goes first
+        for _ in 0..6 {
+            program.remove_input();
+        }
+        assert_eq!(program.input_buffer, b"h\xC3\xA9\x00");
+        program.remove_input();
+        program.remove_input();
+        assert_eq!(program.input_buffer, b"h");
+
+        program.editor.clipboard = Some(vec!["a".to_string(), "b".to_string()]);
+        program.paste_input();
+        program.exit_input_mode(true);
+        assert_eq!(program.engine.input, b"ha\nb");
+    }
+
+    #[test]
+    fn input_files_are_reloaded_on_reset() {
+        let dir = std::env::temp_dir().join(format!("plaque-input-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("input"), dir.join("output"));
+        std::fs::write(&input, b"xy").unwrap();
+
+        let mut program = program(",.,.");
+        program.load_input(input.to_str().unwrap()).unwrap();
+        assert_eq!(program.engine.input, b"xy");
+        program.undo().ok();
+        assert!(program.engine.input.is_empty());
+
+        program.reset();
+        assert_eq!(program.engine.input, b"xy");
+        program.start_running();
+        while program.run_batch(Duration::MAX) {}
+        program.save_output(output.to_str().unwrap()).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), b"xy");
+
+        assert!(program
+            .load_input(dir.join("missing").to_str().unwrap())
+            .is_err());
+        program.load_input("").unwrap();
+        program.reset();
+        assert!(program.engine.input.is_empty());
+
+        // a directory can't be read as input
+        assert!(program.set_input_file(dir.to_str().unwrap()).is_err());
+        assert_eq!(program.input_file, None);
+
+        // a file that goes missing leaves no input behind, rather than the last run's
+        program.set_input_file(input.to_str().unwrap()).unwrap();
+        assert_eq!(program.engine.input, b"xy");
+        std::fs::remove_file(&input).unwrap();
+        program.reset();
+        assert!(program.engine.input.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
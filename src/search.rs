@@ -0,0 +1,172 @@
+use crate::editor::columns;
+use crate::instruction::Instruction;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A match, as the editor cells it covers in order
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Match {
+    /// One cell per grapheme for a text match, or per instruction for an instruction match,
+    /// which skips any comments in between
+    pub cells: Vec<(usize, usize)>,
+}
+
+impl Match {
+    pub fn start(&self) -> (usize, usize) {
+        self.cells[0]
+    }
+
+    /// The position just past the last cell
+    pub fn end(&self) -> (usize, usize) {
+        let (row, col) = self.cells[self.cells.len() - 1];
+        (row, col + 1)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Search {
+    pub query: String,
+    pub replacement: String,
+    /// Match only instruction characters, ignoring comments in the source and the query
+    pub instructions_only: bool,
+    pub matches: Vec<Match>,
+    /// Index of the match at the cursor
+    pub current: Option<usize>,
+    /// Cursor and selection to go back to if the search is cancelled
+    pub origin: ((usize, usize), Option<(usize, usize)>),
+    /// Matches replaced so far in the current replace
+    pub replaced: usize,
+}
+
+impl Search {
+    /// The first match starting at or after a position, or the last one starting before it
+    /// when searching backward, along with whether the search wrapped around the source
+    pub fn find_from(&self, position: (usize, usize), backward: bool) -> Option<(usize, bool)> {
+        let found = if backward {
+            self.matches.iter().rposition(|m| m.start() < position)
+        } else {
+            self.matches.iter().position(|m| m.start() >= position)
+        };
+        match found {
+            Some(i) => Some((i, false)),
+            None if self.matches.is_empty() => None,
+            None if backward => Some((self.matches.len() - 1, true)),
+            None => Some((0, true)),
+        }
+    }
+}
+
+/// Occurrences of `query` within each line, which don't overlap or split a grapheme
+pub fn find_text(lines: &[String], query: &str) -> Vec<Match> {
+    if query.is_empty() {
+        return vec![];
+    }
+
+    let mut matches = vec![];
+    for (row, line) in lines.iter().enumerate() {
+        let boundaries = line
+            .grapheme_indices(true)
+            .map(|(offset, _)| offset)
+            .chain([line.len()])
+            .collect::<Vec<_>>();
+        let mut from = 0;
+        while let Some(found) = line[from..].find(query) {
+            let start = from + found;
+            let end = start + query.len();
+            if boundaries.binary_search(&start).is_err() || boundaries.binary_search(&end).is_err()
+            {
+                from = start + line[start..].chars().next().map_or(1, char::len_utf8);
+                continue;
+            }
+
+            let col = columns(&line[..start]);
+            let cells = (col..col + columns(&line[start..end]))
+                .map(|col| (row, col))
+                .collect();
+            matches.push(Match { cells });
+            from = end;
+        }
+    }
+    matches
+}
+
+/// Occurrences of the instructions in `query`, wherever comments fall between them
+pub fn find_instructions(
+    instructions: &[Instruction],
+    positions: &[(usize, usize)],
+    query: &str,
+) -> Vec<Match> {
+    let pattern = query
+        .chars()
+        .filter_map(Instruction::read)
+        .collect::<Vec<_>>();
+    if pattern.is_empty() {
+        return vec![];
+    }
+
+    let mut matches = vec![];
+    let mut i = 0;
+    while i + pattern.len() <= instructions.len() {
+        if instructions[i..i + pattern.len()] == pattern[..] {
+            let cells = positions[i..i + pattern.len()].to_vec();
+            matches.push(Match { cells });
+            i += pattern.len();
+        } else {
+            i += 1;
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn starts(matches: &[Match]) -> Vec<(usize, usize)> {
+        matches.iter().map(Match::start).collect()
+    }
+
+    #[test]
+    fn finds_text_by_column() {
+        let lines = ["caf\u{E9} +++", "e\u{301}++ ++"].map(String::from);
+        let matches = find_text(&lines, "++");
+        assert_eq!(starts(&matches), vec![(0, 5), (1, 1), (1, 4)]);
+        assert_eq!(matches[1].cells, vec![(1, 1), (1, 2)]);
+
+        // a query can't match half of a grapheme
+        assert_eq!(starts(&find_text(&lines, "e")), vec![]);
+        assert_eq!(starts(&find_text(&lines, "\u{E9}")), vec![(0, 3)]);
+    }
+
+    #[test]
+    fn finds_instructions_across_comments() {
+        let mut program = crate::program::Program::new();
+        program.load_source(">> move\n>+ add >>>+");
+        let matches = find_instructions(
+            &program.engine.instructions,
+            &program.instruction_positions,
+            "three right >>> then +",
+        );
+        assert_eq!(
+            matches.iter().map(|m| m.cells.clone()).collect::<Vec<_>>(),
+            vec![
+                vec![(0, 0), (0, 1), (1, 0), (1, 1)],
+                vec![(1, 7), (1, 8), (1, 9), (1, 10)]
+            ]
+        );
+        assert_eq!(matches[1].end(), (1, 11));
+    }
+
+    #[test]
+    fn wraps_around_the_source() {
+        let lines = ["+ +", "+"].map(String::from);
+        let search = Search {
+            matches: find_text(&lines, "+"),
+            ..Search::default()
+        };
+        assert_eq!(search.find_from((0, 1), false), Some((1, false)));
+        assert_eq!(search.find_from((1, 1), false), Some((0, true)));
+        assert_eq!(search.find_from((0, 2), true), Some((0, false)));
+        assert_eq!(search.find_from((0, 0), true), Some((2, true)));
+    }
+}
@@ -5,10 +5,86 @@ use ratatui::{
     widgets::{Block, Borders, Paragraph},
     Frame,
 };
+use std::collections::HashMap;
 use std::iter;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
+use crate::lint::Severity;
 use crate::program::Program;
 
+const BREAKPOINT_COLOR: Color = Color::Rgb(220, 70, 70);
+const UNCOVERED_COLOR: Color = Color::Rgb(90, 90, 90);
+const MATCH_COLOR: Color = Color::Rgb(110, 100, 40);
+const CURRENT_MATCH_COLOR: Color = Color::Rgb(220, 190, 60);
+const BRACKET_MATCH_COLOR: Color = Color::Rgb(60, 100, 140);
+const UNMATCHED_COLOR: Color = Color::Rgb(130, 30, 30);
+const LINT_ERROR_COLOR: Color = Color::Rgb(240, 90, 90);
+const LINT_WARNING_COLOR: Color = Color::Rgb(230, 180, 60);
+const COLD_COLOR: (f64, f64, f64) = (40.0, 40.0, 70.0);
+const HOT_COLOR: (f64, f64, f64) = (200.0, 70.0, 30.0);
+const TAB_WIDTH: usize = 4;
+
+fn lint_style(severity: Severity) -> Style {
+    let color = match severity {
+        Severity::Error => LINT_ERROR_COLOR,
+        Severity::Warning => LINT_WARNING_COLOR,
+    };
+    Style::default()
+        .fg(color)
+        .add_modifier(Modifier::UNDERLINED)
+}
+
+/// The worst lint severity at each marked cell and on each line, where findings are marked
+/// at their first and last instructions
+fn lint_markers(
+    program: &Program,
+) -> (HashMap<(usize, usize), Severity>, HashMap<usize, Severity>) {
+    let (mut cells, mut lines) = (HashMap::new(), HashMap::new());
+    for finding in &program.lints {
+        let severity = finding.lint.severity();
+        for i in [finding.start, finding.end] {
+            let position = program.instruction_positions[i];
+            let cell = cells.entry(position).or_insert(severity);
+            *cell = std::cmp::min(*cell, severity);
+            let line = lines.entry(position.0).or_insert(severity);
+            *line = std::cmp::min(*line, severity);
+        }
+    }
+    (cells, lines)
+}
+
+/// Split a line into one cell of text per column, with tabs expanded to the next tab stop
+fn line_cells(line: &str) -> Vec<String> {
+    let mut width = 0;
+    line.graphemes(true)
+        .chain(iter::once(" "))
+        .map(|grapheme| {
+            let cell = if grapheme == "\t" {
+                " ".repeat(TAB_WIDTH - width % TAB_WIDTH)
+            } else {
+                grapheme.to_string()
+            };
+            width += cell.width();
+            cell
+        })
+        .collect()
+}
+
+/// Background for an instruction that ran `count` times, on a log scale up to `max`
+fn heat_color(count: u64, max: u64) -> Option<Color> {
+    if count == 0 {
+        return None;
+    }
+    let heat = (count as f64).ln_1p() / (max as f64).ln_1p();
+    let mix = |cold: f64, hot: f64| (cold + (hot - cold) * heat) as u8;
+    Some(Color::Rgb(
+        mix(COLD_COLOR.0, HOT_COLOR.0),
+        mix(COLD_COLOR.1, HOT_COLOR.1),
+        mix(COLD_COLOR.2, HOT_COLOR.2),
+    ))
+}
+
 pub fn render(frame: &mut Frame, area: Rect, program: &mut Program) {
     let cursor_style = Style::default()
         .bg(Color::Rgb(200, 200, 200))
@@ -17,6 +93,20 @@ pub fn render(frame: &mut Frame, area: Rect, program: &mut Program) {
     let focused_code_style = Style::default().add_modifier(Modifier::UNDERLINED);
     let code_style = Style::default();
     let comment_style = Style::default().fg(Color::Rgb(150, 150, 150));
+    let breakpoint_style = Style::default().fg(BREAKPOINT_COLOR);
+    let match_style = Style::default().bg(MATCH_COLOR);
+    let current_match_style = Style::default()
+        .bg(CURRENT_MATCH_COLOR)
+        .fg(Color::Rgb(50, 50, 50));
+    let bracket_match_style = Style::default()
+        .bg(BRACKET_MATCH_COLOR)
+        .add_modifier(Modifier::BOLD);
+    let unmatched_style = Style::default()
+        .bg(UNMATCHED_COLOR)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let uncovered_style = Style::default()
+        .fg(UNCOVERED_COLOR)
+        .add_modifier(Modifier::DIM);
 
     let height = area.height - 2;
     program.editor.set_window_height(height as usize);
@@ -24,6 +114,15 @@ pub fn render(frame: &mut Frame, area: Rect, program: &mut Program) {
         program.editor.window_top_line + program.editor.window_height,
         program.editor.lines.len(),
     );
+    let max_count = program.engine.profile.iter().copied().max().unwrap_or(0);
+    let search_highlights = program.search_highlights();
+    // a bracket at the cursor and its partner are highlighted together
+    let matched_brackets = match program.bracket_at_cursor() {
+        Some((position, Some(partner))) if program.is_editor_mode() => vec![position, partner],
+        _ => vec![],
+    };
+    let unmatched_brackets = program.unmatched_jump_positions();
+    let (lint_cells, lint_lines) = lint_markers(program);
     let line_count = program.editor.lines.len();
     let line_count_digits = (line_count.checked_ilog10().unwrap_or(0) + 1) as usize;
     let text = program
@@ -35,30 +134,82 @@ pub fn render(frame: &mut Frame, area: Rect, program: &mut Program) {
         .enumerate()
         .map(|(i, line)| {
             let i = program.editor.window_top_line + i;
-            let spans = iter::once(Span::styled(
-                format!("{:0>line_count_digits$} ", i + 1),
-                comment_style,
-            ))
-            .chain(
-                line.chars()
-                    .chain(iter::once(' '))
-                    .enumerate()
-                    .map(|(j, chr)| {
-                        let style = if program.is_editor_mode() && program.editor.cursor == (i, j) {
-                            cursor_style
-                        } else if program.is_editor_mode() && program.editor.in_selection(i, j) {
-                            selection_style
-                        } else if program.cursor() == Some((i, j)) {
-                            focused_code_style
-                        } else if program.instruction_positions.contains(&(i, j)) {
-                            code_style
-                        } else {
-                            comment_style
-                        };
+            // a diamond marks lines with conditional breakpoints, and lines without
+            // breakpoints show any lints
+            let gutter_marker = match program.breakpoints_on_line(i).max() {
+                Some(true) => Span::styled("\u{25C6}", breakpoint_style),
+                Some(false) => Span::styled("\u{25CF}", breakpoint_style),
+                None => match lint_lines.get(&i) {
+                    Some(&severity) => Span::styled("!", lint_style(severity)),
+                    None => Span::raw(" "),
+                },
+            };
+            let spans = [
+                Span::styled(format!("{:0>line_count_digits$}", i + 1), comment_style),
+                gutter_marker,
+            ]
+            .into_iter()
+            .chain(line_cells(line).into_iter().enumerate().map(|(j, cell)| {
+                let style = if program.is_editor_mode() && program.editor.cursor == (i, j) {
+                    cursor_style
+                } else if program.is_editor_mode() && program.editor.in_selection(i, j) {
+                    selection_style
+                } else if let Some(&current) = search_highlights.get(&(i, j)) {
+                    if current {
+                        current_match_style
+                    } else {
+                        match_style
+                    }
+                } else if matched_brackets.contains(&(i, j)) {
+                    bracket_match_style
+                } else if unmatched_brackets.contains(&(i, j)) {
+                    unmatched_style
+                } else if program.cursor() == Some((i, j)) {
+                    focused_code_style
+                } else if program.breakpoint_at((i, j)) {
+                    breakpoint_style
+                } else if program.instruction_positions.contains(&(i, j)) {
+                    code_style
+                } else {
+                    comment_style
+                };
+
+                // the cursor, selection and other highlights stay visible over the heat map and
+                // coverage
+                let highlighted = [
+                    cursor_style,
+                    selection_style,
+                    match_style,
+                    current_match_style,
+                    bracket_match_style,
+                    unmatched_style,
+                ]
+                .contains(&style);
+                let index = program
+                    .instruction_index_at((i, j))
+                    .filter(|_| !highlighted);
+                let heat = index
+                    .filter(|_| program.show_profile)
+                    .and_then(|index| heat_color(program.engine.profile[index], max_count));
+                let style = match heat {
+                    Some(color) => style.bg(color),
+                    None => style,
+                };
+                let uncovered = index.is_some_and(|index| {
+                    program.show_coverage && !program.engine.coverage[index].executed()
+                });
+                let style = if uncovered {
+                    style.patch(uncovered_style)
+                } else {
+                    style
+                };
+                let style = match lint_cells.get(&(i, j)) {
+                    Some(&severity) if !highlighted => style.patch(lint_style(severity)),
+                    _ => style,
+                };
 
-                        Span::styled(chr.to_string(), style)
-                    }),
-            )
+                Span::styled(cell, style)
+            }))
             .collect::<Vec<_>>();
 
             Line::from(spans)
@@ -69,3 +220,22 @@ pub fn render(frame: &mut Frame, area: Rect, program: &mut Program) {
 
     frame.render_widget(program, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_lines_into_columns() {
+        assert_eq!(
+            line_cells("e\u{301}\u{1F600}+"),
+            vec!["e\u{301}", "\u{1F600}", "+", " "]
+        );
+        // the emoji is two cells wide, so the tab fills the one left before the stop
+        assert_eq!(
+            line_cells("\u{1F600}a\t+"),
+            vec!["\u{1F600}", "a", " ", "+", " "]
+        );
+        assert_eq!(line_cells("\t"), vec!["    ", " "]);
+    }
+}
@@ -6,7 +6,7 @@ use ratatui::{
 };
 use std::cmp::max;
 
-use crate::program::Mode;
+use crate::program::{Mode, Program, Prompt};
 
 #[derive(Debug)]
 struct HelpItem<'a> {
@@ -27,32 +27,135 @@ impl HelpItem<'_> {
     }
 }
 
-pub fn render(frame: &mut Frame, area: Rect, mode: Mode) {
+/// Editor keys grouped into pages, each narrow enough for an 80 column terminal
+fn editor_pages() -> [(&'static str, Vec<HelpItem<'static>>); 6] {
+    [
+        (
+            "editing",
+            vec![
+                HelpItem::new("esc", "Done"),
+                HelpItem::new("↑↓←→", "Move Cursor"),
+                HelpItem::new("ctrl+s", "Save"),
+                HelpItem::new("ctrl+c/x/v", "Copy/Cut/Paste"),
+                HelpItem::new("ctrl/alt+z", "Undo/Redo Edit"),
+                HelpItem::new("bksp/del", "Delete"),
+                HelpItem::new("tab", "Indent"),
+            ],
+        ),
+        (
+            "stepping",
+            vec![
+                HelpItem::new("ctrl+p", "Play"),
+                HelpItem::new("ctrl/alt+o", "Step Over/Back"),
+                HelpItem::new("ctrl/alt+u", "Step Out/Back"),
+                HelpItem::new("ctrl/alt+t", "Run/Undo to Cursor"),
+            ],
+        ),
+        (
+            "breakpoints",
+            vec![
+                HelpItem::new("ctrl+b", "Breakpoint"),
+                HelpItem::new("ctrl+k", "Condition"),
+                HelpItem::new("ctrl+w", "Watch Cell"),
+                HelpItem::new("ctrl+f", "Profile"),
+                HelpItem::new("ctrl+g", "Coverage"),
+                HelpItem::new("ctrl+l", "Save Coverage"),
+            ],
+        ),
+        (
+            "tape",
+            vec![
+                HelpItem::new("ctrl+d", "Cell Display"),
+                HelpItem::new("alt+,.", "Scroll Tape"),
+                HelpItem::new("pgup/dn", "Page Tape"),
+                HelpItem::new("ctrl+n", "Go to Cell"),
+                HelpItem::new("home", "Follow Pointer"),
+                HelpItem::new("ctrl+y", "Set Cell"),
+                HelpItem::new("ctrl+a", "Move Pointer"),
+            ],
+        ),
+        (
+            "input and output",
+            vec![
+                HelpItem::new("ctrl+q", "Edit Input"),
+                HelpItem::new("alt+i", "Load Input"),
+                HelpItem::new("ctrl+e", "EOF Mode"),
+                HelpItem::new("alt+s", "Save Output"),
+                HelpItem::new("alt+h", "Hex Output"),
+            ],
+        ),
+        (
+            "search",
+            vec![
+                HelpItem::new("alt+f", "Search"),
+                HelpItem::new("alt+n/p", "Next/Prev Match"),
+                HelpItem::new("alt+r", "Replace"),
+                HelpItem::new("alt+m", "Matching Bracket"),
+            ],
+        ),
+    ]
+}
+
+pub fn render(frame: &mut Frame, area: Rect, program: &Program) {
     let height = 3;
+    let mode = program.mode;
+    let mut editor_pages = editor_pages();
+    let page = program.help_page % editor_pages.len();
 
-    let title = format!(
-        "Help ({})",
-        match mode {
-            Mode::Editor => "editor mode",
-            Mode::Input => "input mode",
-        }
-    );
+    let title = match mode {
+        Mode::Editor => format!(
+            "Help (editor mode, {} {}/{}, f1 for more)",
+            editor_pages[page].0,
+            page + 1,
+            editor_pages.len()
+        ),
+        Mode::Input => "Help (input mode)".to_string(),
+        Mode::Running => "Help (running)".to_string(),
+        Mode::Prompt(_) => "Help (prompt)".to_string(),
+        Mode::Playing => format!(
+            "Help (playing {} at {} steps/s)",
+            if program.play_backwards {
+                "backwards"
+            } else {
+                "forwards"
+            },
+            program.steps_per_second()
+        ),
+    };
 
     let help_items: Vec<HelpItem> = match mode {
-        Mode::Editor => vec![
-            HelpItem::new("esc", "Done"),
-            HelpItem::new("↑↓←→", "Move Cursor"),
-            HelpItem::new("ctrl+s", "Save"),
-            HelpItem::new("ctrl+c", "Copy"),
-            HelpItem::new("ctrl+x", "Cut"),
-            HelpItem::new("ctrl+v", "Paste"),
-            HelpItem::new("bksp", "Backward Delete"),
-            HelpItem::new("del", "Forward Delete"),
-            HelpItem::new("tab", "Indent"),
-        ],
+        Mode::Editor => std::mem::take(&mut editor_pages[page].1),
         Mode::Input => vec![
             HelpItem::new("enter", "Submit"),
             HelpItem::new("shift+enter", "Newline"),
+            HelpItem::new("\\xNN", "Raw Byte"),
+            HelpItem::new("ctrl+v", "Paste"),
+            HelpItem::new("esc", "Cancel"),
+        ],
+        Mode::Prompt(Prompt::Search | Prompt::InstructionSearch) => vec![
+            HelpItem::new("enter", "Accept"),
+            HelpItem::new("↑↓", "Prev/Next Match"),
+            HelpItem::new("tab", "Instructions Only"),
+            HelpItem::new("esc", "Cancel"),
+        ],
+        Mode::Prompt(Prompt::ConfirmReplace) => vec![
+            HelpItem::new("y", "Replace"),
+            HelpItem::new("n", "Skip"),
+            HelpItem::new("a", "Replace All"),
+            HelpItem::new("esc", "Stop"),
+        ],
+        Mode::Prompt(_) => vec![
+            HelpItem::new("enter", "Submit"),
+            HelpItem::new("esc", "Cancel"),
+        ],
+        Mode::Playing => vec![
+            HelpItem::new("space", "Pause"),
+            HelpItem::new("↑↓", "Speed"),
+            HelpItem::new("←→", "Direction"),
+        ],
+        Mode::Running => vec![
+            HelpItem::new("ctrl+c", "Stop"),
+            HelpItem::new("esc", "Stop"),
         ],
     };
 
@@ -94,3 +197,37 @@ pub fn render(frame: &mut Frame, area: Rect, mode: Mode) {
 
     frame.render_widget(table, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    #[test]
+    fn editor_pages_fit_in_80_columns() {
+        let mut terminal = Terminal::new(TestBackend::new(80, 5)).unwrap();
+        let mut program = Program::new();
+        for (_, items) in editor_pages() {
+            terminal
+                .draw(|frame| render(frame, frame.area(), &program))
+                .unwrap();
+            let buffer = terminal.backend().buffer();
+            let text = (0..buffer.area.height)
+                .map(|y| {
+                    (0..buffer.area.width)
+                        .map(|x| buffer[(x, y)].symbol())
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            for item in items {
+                assert!(
+                    text.contains(item.label),
+                    "{} clipped in\n{text}",
+                    item.label
+                );
+            }
+            program.next_help_page();
+        }
+    }
+}
@@ -9,6 +9,7 @@ use ratatui::{
 use crate::program::{Mode, Program};
 
 const NEWLINE_COLOR: Color = Color::Rgb(80, 80, 80);
+const ESCAPE_COLOR: Color = Color::Rgb(220, 160, 60);
 
 pub fn render(frame: &mut Frame, area: Rect, program: &Program) {
     let input_output_height = area.height - 6;
@@ -33,23 +34,58 @@ pub fn render(frame: &mut Frame, area: Rect, program: &Program) {
     render_debug(frame, panel[2], program);
 }
 
-/// Display Input/Output text
-fn io_text(buffer: &[u8]) -> Text {
-    let text = std::str::from_utf8(buffer).unwrap();
-    let newlines = text.matches('\n').count();
-    let lines = text
-        .split('\n')
-        .enumerate()
+/// Display Input/Output text, with any bytes that aren't valid UTF-8 escaped
+fn io_text(buffer: &[u8]) -> Text<'_> {
+    let newline_style = Style::default().fg(NEWLINE_COLOR);
+    let escape_style = Style::default().fg(ESCAPE_COLOR);
+    let mut lines = vec![];
+    let mut line = vec![];
+
+    for chunk in buffer.utf8_chunks() {
         // add line ending marker B6 to each line except the last
-        .map(|(i, line)| {
-            if i != newlines {
-                Line::from(vec![
-                    Span::from(line),
-                    Span::styled("\u{B6}", Style::default().fg(NEWLINE_COLOR)),
-                ])
-            } else {
-                Line::from(vec![Span::from(line)])
+        for (i, part) in chunk.valid().split('\n').enumerate() {
+            if i > 0 {
+                line.push(Span::styled("\u{B6}", newline_style));
+                lines.push(Line::from(std::mem::take(&mut line)));
+            }
+            if !part.is_empty() {
+                line.push(Span::from(part));
             }
+        }
+
+        let invalid = chunk
+            .invalid()
+            .iter()
+            .map(|byte| format!("\\x{byte:02X}"))
+            .collect::<String>();
+        if !invalid.is_empty() {
+            line.push(Span::styled(invalid, escape_style));
+        }
+    }
+    lines.push(Line::from(line));
+
+    Text::from(lines)
+}
+
+/// Display bytes as rows of hex, each starting with the offset of its first byte
+fn hex_dump(buffer: &[u8], width: usize) -> Text<'_> {
+    // an offset, then three characters per byte
+    let bytes_per_line = std::cmp::max(1, width.saturating_sub(6) / 3);
+    let offset_style = Style::default().fg(NEWLINE_COLOR);
+
+    let lines = buffer
+        .chunks(bytes_per_line)
+        .enumerate()
+        .map(|(i, bytes)| {
+            let hex = bytes
+                .iter()
+                .map(|byte| format!("{byte:02X}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            Line::from(vec![
+                Span::styled(format!("{:04X}: ", i * bytes_per_line), offset_style),
+                Span::raw(hex),
+            ])
         })
         .collect::<Vec<_>>();
 
@@ -58,10 +94,20 @@ fn io_text(buffer: &[u8]) -> Text {
 
 pub fn render_input(frame: &mut Frame, area: Rect, program: &Program) {
     let text = match program.mode {
-        Mode::Input => &program.input_buffer,
-        _ => &program.engine.input,
+        Mode::Input => {
+            // show an escape that's still being typed after the rest of the input
+            let mut text = io_text(&program.input_buffer);
+            if let Some(line) = text.lines.last_mut() {
+                line.push_span(Span::styled(
+                    program.input_escape.as_str(),
+                    Style::default().fg(ESCAPE_COLOR),
+                ));
+            }
+            text
+        }
+        _ => io_text(&program.engine.input),
     };
-    let input = Paragraph::new(io_text(text))
+    let input = Paragraph::new(text)
         .block(Block::default().title("Input").borders(Borders::ALL))
         .wrap(Wrap { trim: false });
 
@@ -69,8 +115,14 @@ pub fn render_input(frame: &mut Frame, area: Rect, program: &Program) {
 }
 
 pub fn render_output(frame: &mut Frame, area: Rect, program: &Program) {
-    let output = Paragraph::new(io_text(&program.engine.output))
-        .block(Block::default().title("Output").borders(Borders::ALL))
+    let (text, title) = if program.show_output_hex {
+        let width = area.width.saturating_sub(2) as usize;
+        (hex_dump(&program.engine.output, width), "Output (hex)")
+    } else {
+        (io_text(&program.engine.output), "Output")
+    };
+    let output = Paragraph::new(text)
+        .block(Block::default().title(title).borders(Borders::ALL))
         .wrap(Wrap { trim: false });
 
     frame.render_widget(output, area);
@@ -88,3 +140,46 @@ fn render_debug(frame: &mut Frame, area: Rect, program: &Program) {
 
     frame.render_widget(debug, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(text: &Text) -> Vec<String> {
+        text.lines
+            .iter()
+            .map(|line| {
+                line.spans
+                    .iter()
+                    .map(|span| span.content.as_ref())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn escapes_invalid_utf8() {
+        let text = io_text(b"caf\xC3\xA9\n\xFF\xFEok\n");
+        assert_eq!(
+            contents(&text),
+            vec!["caf\u{E9}\u{B6}", "\\xFF\\xFEok\u{B6}", ""]
+        );
+        assert_eq!(text.lines[1].spans[0].style.fg, Some(ESCAPE_COLOR));
+
+        // a multi-byte character cut short is escaped rather than dropped
+        assert_eq!(contents(&io_text(b"a\xE2\x82")), vec!["a\\xE2\\x82"]);
+    }
+
+    #[test]
+    fn dumps_hex_to_fit_the_width() {
+        let text = hex_dump(b"Hello, world", 18);
+        assert_eq!(
+            contents(&text),
+            vec![
+                "0000: 48 65 6C 6C",
+                "0004: 6F 2C 20 77",
+                "0008: 6F 72 6C 64"
+            ]
+        );
+    }
+}
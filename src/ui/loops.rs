@@ -0,0 +1,45 @@
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+
+use crate::program::Program;
+
+const ITERATIONS_COLOR: Color = Color::Rgb(120, 120, 120);
+
+/// Lists the loops around the instruction pointer, innermost at the bottom, like a call stack
+pub fn render(frame: &mut Frame, area: Rect, program: &Program) {
+    let loop_stack = &program.engine.loop_stack;
+    // keep the innermost loops in view when they don't all fit
+    let visible = area.height.saturating_sub(2) as usize;
+    let skip = loop_stack.len().saturating_sub(visible);
+
+    let lines = loop_stack
+        .iter()
+        .enumerate()
+        .skip(skip)
+        .filter_map(|(depth, loop_frame)| {
+            let &(line, column) = program.instruction_positions.get(loop_frame.open)?;
+            Some(Line::from(vec![
+                Span::raw(format!(
+                    "{:indent$}[ {}:{}",
+                    "",
+                    line + 1,
+                    column + 1,
+                    indent = depth
+                )),
+                Span::styled(
+                    format!(" ×{}", loop_frame.iterations),
+                    Style::default().fg(ITERATIONS_COLOR),
+                ),
+            ]))
+        })
+        .collect::<Vec<_>>();
+
+    let loops = Paragraph::new(lines).block(Block::default().title("Loops").borders(Borders::ALL));
+
+    frame.render_widget(loops, area);
+}
@@ -1,16 +1,18 @@
 mod editor;
 mod help;
 mod io;
+mod loops;
 mod tape;
 
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::Paragraph,
     Frame,
 };
 
-use crate::program::Program;
+use crate::program::{Mode, Program};
 
 pub fn draw(program: &mut Program, frame: &mut Frame) {
     let size = frame.area();
@@ -32,17 +34,29 @@ pub fn draw(program: &mut Program, frame: &mut Frame) {
     let top_panel = Layout::default()
         .direction(Direction::Horizontal)
         .margin(0)
-        .constraints([Constraint::Min(10), Constraint::Length(30)].as_ref())
+        .constraints(
+            [
+                Constraint::Min(10),
+                Constraint::Length(20),
+                Constraint::Length(30),
+            ]
+            .as_ref(),
+        )
         .split(window[1]);
 
     render_filename(frame, window[0], program);
     editor::render(frame, top_panel[0], program);
-    io::render(frame, top_panel[1], program);
+    loops::render(frame, top_panel[1], program);
+    io::render(frame, top_panel[2], program);
     tape::render(frame, window[2], program);
-    help::render(frame, window[3], program.mode);
+    help::render(frame, window[3], program);
 }
 
 fn render_filename(frame: &mut Frame, area: Rect, program: &Program) {
+    if let Mode::Prompt(prompt) = program.mode {
+        return render_prompt(frame, area, prompt.label(), &program.prompt_text);
+    }
+
     let filename = program
         .editor
         .filepath
@@ -65,3 +79,22 @@ fn render_filename(frame: &mut Frame, area: Rect, program: &Program) {
 
     frame.render_widget(paragraph, area);
 }
+
+fn render_prompt(frame: &mut Frame, area: Rect, label: &str, text: &str) {
+    let line = Line::from(vec![
+        Span::styled(
+            format!(" {label}: "),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(text.to_string()),
+        Span::styled(" ", Style::default().bg(Color::Rgb(50, 50, 50))),
+    ]);
+
+    let paragraph = Paragraph::new(line).style(
+        Style::default()
+            .bg(Color::Rgb(200, 200, 200))
+            .fg(Color::Rgb(50, 50, 50)),
+    );
+
+    frame.render_widget(paragraph, area);
+}
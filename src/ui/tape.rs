@@ -1,65 +1,193 @@
 use ratatui::{
     layout::{Alignment, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph},
     Frame,
 };
 
-use crate::program::Program;
+use crate::config::CellWidth;
+use crate::program::{CellDisplay, Program};
 
 const CELL_COLOR: Color = Color::Rgb(255, 255, 255);
 const INDEX_COLOR: Color = Color::Rgb(150, 150, 150);
 const EMPTY_COLOR: Color = Color::Rgb(80, 80, 80);
+const WATCHED_COLOR: Color = Color::Rgb(220, 70, 70);
 
-pub fn render(frame: &mut Frame, area: Rect, program: &Program) {
-    let tape_pointer = program.engine.tape_pointer;
-    let tape_length = program.engine.tape.len();
-    let tape_space = TapeSpace::new(frame.area().width as usize - 2, tape_pointer, tape_length);
-    let right_slots = tape_space.used_right_slots + tape_space.unused_right_slots;
+pub fn render(frame: &mut Frame, area: Rect, program: &mut Program) {
+    let engine = &program.engine;
+    let display = program.cell_display;
+    let bits = engine.config.cell_width;
+    let cell_width = display_width(display, bits);
+    let width = (area.width as usize).saturating_sub(2);
+
+    let range = engine.cell_range();
+    let (first, last) = (*range.start(), *range.end());
+    let center = program.tape_view_cell().clamp(first, last);
+    let pointer = engine.cell_index(engine.tape_pointer);
+
+    // only the cells that can fit either side matter, which keeps this within a usize
+    let (left_cells, right_cells) = (
+        (center as i128 - first as i128).min(width as i128) as usize,
+        (last as i128 - center as i128).min(width as i128) as usize,
+    );
+    let tape_space = TapeSpace::new(width, left_cells, left_cells + 1 + right_cells, cell_width);
+    let empty_blob = "-".repeat(cell_width);
+    let blank_blob = " ".repeat(cell_width);
 
     let cell_style = Style::default().fg(CELL_COLOR);
     let index_style = Style::default().fg(INDEX_COLOR);
     let empty_style = Style::default().fg(EMPTY_COLOR);
+    let watched_style = Style::default().fg(WATCHED_COLOR);
+    let viewed_style = Style::default().add_modifier(Modifier::REVERSED);
+    let is_watched = |index: isize| engine.watchpoints.iter().any(|w| w.cell == index);
+
+    let indices = center - tape_space.used_left_slots as isize
+        ..=center + tape_space.used_right_slots as isize;
+    let empty_left = || {
+        [empty_blob.as_str()]
+            .repeat(tape_space.unused_left_slots)
+            .into_iter()
+            .map(|blob| Span::styled(blob, empty_style))
+    };
+    let empty_right = || {
+        [empty_blob.as_str()]
+            .repeat(tape_space.unused_right_slots)
+            .into_iter()
+            .map(|blob| Span::styled(blob, empty_style))
+    };
 
-    let tape_iter = program.engine.tape.iter();
-    let mut cells = ["---"]
+    let mut markers = [blank_blob.as_str()]
         .repeat(tape_space.unused_left_slots)
         .into_iter()
-        .map(|blob| Span::styled(blob, empty_style))
+        .map(Span::raw)
+        .chain(indices.clone().map(|index| {
+            if index == pointer {
+                Span::raw(format!("{:^cell_width$}", "\u{25BC}"))
+            } else {
+                Span::raw(blank_blob.clone())
+            }
+        }))
         .chain(
-            tape_iter
-                .chain([&0u8].repeat(tape_space.unused_right_slots))
-                .skip(tape_pointer - tape_space.used_left_slots)
-                .take(tape_space.used_left_slots + 1 + right_slots)
-                .map(|cell| Span::styled(format!("{cell:0>3}"), cell_style)),
+            [blank_blob.as_str()]
+                .repeat(tape_space.unused_right_slots)
+                .into_iter()
+                .map(Span::raw),
         )
         .collect::<Vec<Span>>();
 
-    let mut indexes = ["---"]
-        .repeat(tape_space.unused_left_slots)
-        .into_iter()
-        .map(|blob| Span::styled(blob, empty_style))
-        .chain(
-            (tape_pointer - tape_space.used_left_slots..tape_pointer + 1 + right_slots)
-                .map(|i| Span::styled(format!("{:0>3}", i % 1000), index_style)),
-        )
+    let mut cells = empty_left()
+        .chain(indices.clone().map(|index| {
+            // cells that haven't been allocated yet are still zero
+            let cell = engine.cell_at(index).unwrap_or(0);
+            let style = if program.tape_view.is_some() && index == center {
+                viewed_style
+            } else if is_watched(index) {
+                watched_style
+            } else {
+                cell_style
+            };
+            Span::styled(format_cell(cell, display, bits), style)
+        }))
+        .chain(empty_right())
+        .collect::<Vec<Span>>();
+
+    let mut indexes = empty_left()
+        .chain(indices.map(|index| Span::styled(format_index(index, cell_width), index_style)))
+        .chain(empty_right())
         .collect::<Vec<Span>>();
 
     let text = vec![
-        Line::from("\u{25BC}"),
-        join_tape_spans(cells.as_mut(), &tape_space),
-        join_tape_spans(indexes.as_mut(), &tape_space),
+        join_tape_spans(markers.as_mut(), &tape_space, " "),
+        join_tape_spans(cells.as_mut(), &tape_space, "|"),
+        join_tape_spans(indexes.as_mut(), &tape_space, "|"),
     ];
 
+    let title = match program.tape_view {
+        Some(cell) => format!("Tape ({display}, viewing cell {cell})"),
+        None => format!("Tape ({display})"),
+    };
+    let summary = match engine.highest_nonzero_cell() {
+        Some(cell) => format!(
+            " length {}, highest non-zero cell {cell} ",
+            engine.tape.len()
+        ),
+        None => format!(" length {}, all zero ", engine.tape.len()),
+    };
+
     let tape = Paragraph::new(text)
-        .block(Block::default().title("Tape").borders(Borders::ALL))
+        .block(
+            Block::default()
+                .title(title)
+                .title_bottom(Line::from(summary).right_aligned())
+                .borders(Borders::ALL),
+        )
         .alignment(Alignment::Center);
 
     frame.render_widget(tape, area);
+
+    program.tape_page = tape_space.used_left_slots
+        + tape_space.unused_left_slots
+        + tape_space.used_right_slots
+        + tape_space.unused_right_slots;
+}
+
+/// Number of characters needed to show any cell value
+fn display_width(display: CellDisplay, cell_width: CellWidth) -> usize {
+    match display {
+        CellDisplay::Decimal => cell_width.digits(),
+        CellDisplay::Hex => cell_width.bits() as usize / 4,
+        // the most negative value, with its sign
+        CellDisplay::Signed => (cell_width.max() / 2 + 1).ilog10() as usize + 2,
+        // the longest escape is `\x` and the value in hex
+        CellDisplay::Ascii => cell_width.bits() as usize / 4 + 2,
+        CellDisplay::Binary => cell_width.bits() as usize,
+    }
+}
+
+/// Formats a cell value padded to its display width
+fn format_cell(value: u32, display: CellDisplay, cell_width: CellWidth) -> String {
+    let width = display_width(display, cell_width);
+    match display {
+        CellDisplay::Decimal => format!("{value:0>width$}"),
+        CellDisplay::Hex => format!("{value:0>width$X}"),
+        CellDisplay::Signed => {
+            // shift the sign bit of the cell into the top of an i64
+            let shift = 64 - cell_width.bits();
+            let signed = ((value as i64) << shift) >> shift;
+            format!("{signed:>width$}")
+        }
+        CellDisplay::Ascii => {
+            let text = match value {
+                0 => "\\0".to_string(),
+                9 => "\\t".to_string(),
+                10 => "\\n".to_string(),
+                13 => "\\r".to_string(),
+                0x20..=0x7E => char::from(value as u8).to_string(),
+                _ => format!("\\x{value:02X}"),
+            };
+            format!("{text:>width$}")
+        }
+        CellDisplay::Binary => format!("{value:0>width$b}"),
+    }
+}
+
+/// Formats the low digits of a cell index to fit the cell width, keeping the sign of negative indices
+fn format_index(index: isize, width: usize) -> String {
+    let digits = if index < 0 { width - 1 } else { width };
+    let modulus = 10usize.pow(digits as u32);
+    let low_digits = index.unsigned_abs() % modulus;
+
+    if index < 0 {
+        format!("-{low_digits:0>digits$}")
+    } else {
+        format!("{low_digits:0>digits$}")
+    }
 }
 
 struct TapeSpace {
+    cell_width: usize,
+
     used_left_slots: usize,
     unused_left_slots: usize,
     left_overflow: usize,
@@ -70,11 +198,14 @@ struct TapeSpace {
 }
 
 impl TapeSpace {
-    fn new(width: usize, tape_pointer: usize, tape_length: usize) -> TapeSpace {
-        let available = width - 3;
+    fn new(width: usize, tape_pointer: usize, tape_length: usize, cell_width: usize) -> TapeSpace {
+        // each slot is a cell plus its separator
+        let slot_width = cell_width + 1;
+        // a panel narrower than one cell still shows the cell under the pointer
+        let available = width.saturating_sub(cell_width);
         let (half, remainder) = (available / 2, available % 2);
         let (left, right) = (half + remainder, half);
-        let (left_slots, right_slots) = (left.div_ceil(4), right.div_ceil(4));
+        let (left_slots, right_slots) = (left.div_ceil(slot_width), right.div_ceil(slot_width));
 
         let used_left_slots = std::cmp::min(left_slots, tape_pointer);
         let unused_left_slots = left_slots - used_left_slots;
@@ -82,9 +213,14 @@ impl TapeSpace {
         let used_right_slots = std::cmp::min(right_slots, tape_length - tape_pointer - 1);
         let unused_right_slots = right_slots - used_right_slots;
 
-        let (left_overflow, right_overflow) = ((left_slots * 4) - left, (right_slots * 4) - right);
+        let (left_overflow, right_overflow) = (
+            (left_slots * slot_width) - left,
+            (right_slots * slot_width) - right,
+        );
 
         TapeSpace {
+            cell_width,
+
             used_left_slots,
             unused_left_slots,
             left_overflow,
@@ -96,21 +232,31 @@ impl TapeSpace {
     }
 }
 
-fn join_tape_spans<'a>(spans: &mut [Span<'a>], tape_space: &TapeSpace) -> Line<'a> {
+fn join_tape_spans<'a>(
+    spans: &mut [Span<'a>],
+    tape_space: &TapeSpace,
+    separator: &'a str,
+) -> Line<'a> {
     let len = spans.len();
 
     // remove any overflow from the first and last elements
-    spans[0].content = spans[0].content[..3 - tape_space.left_overflow]
-        .to_string()
+    spans[0].content = spans[0]
+        .content
+        .chars()
+        .take(tape_space.cell_width - tape_space.left_overflow)
+        .collect::<String>()
         .into();
-    spans[len - 1].content = spans[len - 1].content[tape_space.right_overflow..]
-        .to_string()
+    spans[len - 1].content = spans[len - 1]
+        .content
+        .chars()
+        .skip(tape_space.right_overflow)
+        .collect::<String>()
         .into();
 
     let joined = spans
         .iter()
         .cloned()
-        .intersperse(Span::styled("|", Style::default().fg(EMPTY_COLOR)))
+        .intersperse(Span::styled(separator, Style::default().fg(EMPTY_COLOR)))
         .collect::<Vec<Span>>();
 
     Line::from(joined)
@@ -120,21 +266,76 @@ fn join_tape_spans<'a>(spans: &mut [Span<'a>], tape_space: &TapeSpace) -> Line<'
 mod tests {
     use super::*;
 
+    #[test]
+    fn cell_formatting() {
+        let format = |value, display| format_cell(value, display, CellWidth::U8);
+        assert_eq!(format(65, CellDisplay::Decimal), "065");
+        assert_eq!(format(255, CellDisplay::Hex), "FF");
+        assert_eq!(format(255, CellDisplay::Signed), "  -1");
+        assert_eq!(format(128, CellDisplay::Signed), "-128");
+        assert_eq!(format(127, CellDisplay::Signed), " 127");
+        assert_eq!(format(65, CellDisplay::Ascii), "   A");
+        assert_eq!(format(10, CellDisplay::Ascii), "  \\n");
+        assert_eq!(format(200, CellDisplay::Ascii), "\\xC8");
+        assert_eq!(format(5, CellDisplay::Binary), "00000101");
+
+        let wide = |value, display| format_cell(value, display, CellWidth::U16);
+        assert_eq!(wide(65535, CellDisplay::Signed), "    -1");
+        assert_eq!(wide(32768, CellDisplay::Signed), "-32768");
+        assert_eq!(wide(0x1234, CellDisplay::Ascii), "\\x1234");
+        assert_eq!(
+            format_cell(u32::MAX, CellDisplay::Signed, CellWidth::U32),
+            "         -1"
+        );
+    }
+
+    #[test]
+    fn index_formatting() {
+        assert_eq!(format_index(7, 3), "007");
+        assert_eq!(format_index(1234, 3), "234");
+        assert_eq!(format_index(-7, 3), "-07");
+        assert_eq!(format_index(-1234, 5), "-1234");
+        assert_eq!(format_index(-12345, 5), "-2345");
+    }
+
     #[test]
     fn overflow() {
         #[rustfmt::skip]
         let overflows = [(0, 0), (0, 1), (1, 1), (1, 2), (2, 2), (2, 3), (3, 3), (3, 0)];
         for i in 0..16 {
-            let tape_space = TapeSpace::new(27 - i, 0, 1);
+            let tape_space = TapeSpace::new(27 - i, 0, 1, 3);
             assert_eq!(tape_space.left_overflow, overflows[i % 8].0);
             assert_eq!(tape_space.right_overflow, overflows[i % 8].1);
         }
     }
 
+    #[test]
+    fn overflow_with_wide_cells() {
+        #[rustfmt::skip]
+        let overflows = [(0, 0), (0, 1), (1, 1), (1, 2), (2, 2), (2, 3), (3, 3), (3, 4), (4, 4), (4, 5), (5, 5), (5, 0)];
+        for i in 0..24 {
+            let tape_space = TapeSpace::new(41 - i, 0, 1, 5);
+            assert_eq!(tape_space.left_overflow, overflows[i % 12].0);
+            assert_eq!(tape_space.right_overflow, overflows[i % 12].1);
+        }
+    }
+
+    #[test]
+    fn narrower_than_a_cell() {
+        let tape_space = TapeSpace::new(10, 5, 20, 32);
+        assert_eq!(tape_space.used_left_slots + tape_space.unused_left_slots, 0);
+        assert_eq!(
+            tape_space.used_right_slots + tape_space.unused_right_slots,
+            0
+        );
+        assert_eq!(tape_space.left_overflow, 0);
+        assert_eq!(tape_space.right_overflow, 0);
+    }
+
     #[test]
     fn overflow_unaffected_by_tape_position() {
         for i in 0..16 {
-            let tape_space = TapeSpace::new(24, i, 100);
+            let tape_space = TapeSpace::new(24, i, 100, 3);
             assert_eq!(tape_space.left_overflow, 1);
             assert_eq!(tape_space.right_overflow, 2);
         }
@@ -144,7 +345,7 @@ mod tests {
     fn slots_by_tape_position() {
         let used_slots = [(0, 3), (1, 3), (2, 3), (3, 3), (3, 2), (3, 1), (3, 0)];
         for (i, used_slot) in used_slots.iter().enumerate() {
-            let tape_space = TapeSpace::new(27, i, 7);
+            let tape_space = TapeSpace::new(27, i, 7, 3);
             assert_eq!(tape_space.used_left_slots, used_slot.0);
             assert_eq!(tape_space.used_right_slots, used_slot.1);
             assert_eq!(tape_space.unused_left_slots, 3 - used_slot.0);
@@ -157,7 +358,7 @@ mod tests {
         #[rustfmt::skip]
         let unused_slots = [(1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 0), (0, 0)];
         for (i, unused_slot) in unused_slots.iter().enumerate() {
-            let tape_space = TapeSpace::new(19 - i, 1, 3);
+            let tape_space = TapeSpace::new(19 - i, 1, 3, 3);
             assert_eq!(tape_space.used_left_slots, 1);
             assert_eq!(tape_space.used_right_slots, 1);
             assert_eq!(tape_space.unused_left_slots, unused_slot.0);